@@ -1,13 +1,24 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::ffi::OsString;
 use std::path::PathBuf;
 
 const DEFAULT_COMMIT_MESSAGE_FOR_ADD: &str = "Adding new dotfiles";
 const DEFAULT_COMMIT_MESSAGE_FOR_UPDATE: &str = "Updating dotfiles";
 const DEFAULT_COMMIT_MESSAGE_FOR_REMOVE: &str = "Removing dotfiles";
-const DEFAULT_PROFILE: &str = "main";
+const DEFAULT_COMMIT_MESSAGE_FOR_MOVE: &str = "Moving dotfiles";
+const DEFAULT_COMMIT_MESSAGE_FOR_CONVERT: &str = "Converting the configuration";
+const DEFAULT_COMMIT_MESSAGE_FOR_ENTRY: &str = "Updating entry options";
+const DEFAULT_COMMIT_MESSAGE_FOR_SETTINGS: &str = "Updating settings";
+const DEFAULT_COMMIT_MESSAGE_FOR_RELAYOUT: &str = "Changing the repository layout";
+const DEFAULT_COMMIT_MESSAGE_FOR_DOCTOR: &str = "Fixing problems found by twist doctor";
+const DEFAULT_COMMIT_MESSAGE_FOR_GC: &str = "Removing orphaned files";
+const DEFAULT_COMMIT_MESSAGE_FOR_INIT: &str = "Initializing the dotfiles repository";
+const DEFAULT_COMMIT_MESSAGE_FOR_ADOPT: &str = "Adopting the dotfiles repository";
+const DEFAULT_COMMIT_MESSAGE_FOR_IMPORT: &str = "Importing dotfiles";
+const DEFAULT_COMMIT_MESSAGE_FOR_DISCOVER: &str = "Adding discovered dotfiles";
+pub const DEFAULT_PROFILE: &str = "main";
 const PROFILE_ENV: &str = "TWIST_PROFILE";
-const ROOT_DIR_ENV: &str = "TWIST_ROOTDIR";
+pub const ROOT_DIR_ENV: &str = "TWIST_ROOTDIR";
 
 #[derive(Debug, Parser)]
 #[command(
@@ -18,12 +29,24 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: CliCommand,
 
-    #[arg(long, short, env = PROFILE_ENV, default_value = DEFAULT_PROFILE, help = "Set the profile used")]
-    pub profile: String,
+    #[arg(
+        long,
+        short,
+        env = PROFILE_ENV,
+        help = "Set the profile used, defaults to the default-profile setting or main"
+    )]
+    pub profile: Option<String>,
 
     #[arg(global = true, long = "root-dir", env = ROOT_DIR_ENV, help = "Override the default root directory")]
     pub root_dir_override: Option<PathBuf>,
 
+    #[arg(
+        global = true,
+        long,
+        help = "Commit without validating the syntax of the changed files"
+    )]
+    pub no_verify: bool,
+
     #[arg(long, short, help = "Enable verbose logging")]
     pub verbose: bool,
 }
@@ -46,6 +69,42 @@ pub enum CliCommand {
     )]
     RemoveFiles(RemoveFilesCliArgs),
 
+    #[command(
+        about = "Moves a tracked file or directory to a new location",
+        name = "move",
+        alias = "mv"
+    )]
+    MoveEntry(MoveEntryCliArgs),
+
+    #[command(
+        about = "Finds well-known dotfiles that are not tracked yet and offers to add them",
+        name = "discover"
+    )]
+    Discover(DiscoverCliArgs),
+
+    #[command(
+        about = "Lists the tracked files and directories",
+        name = "list",
+        alias = "ls"
+    )]
+    ListEntries(ListEntriesCliArgs),
+
+    #[command(
+        about = "Shows or changes the options of a tracked file or directory",
+        name = "entry"
+    )]
+    Entry(EntryCliArgs),
+
+    #[command(about = "Manages the configuration file", name = "config")]
+    Config(ConfigCliArgs),
+
+    #[command(
+        about = "Merges two versions of the configuration, used as a git merge driver",
+        name = "merge-config",
+        hide = true
+    )]
+    MergeConfig(MergeConfigCliArgs),
+
     #[command(about = "Applies the dotfiles to the current system", name = "apply")]
     ApplyFiles(ApplyFilesCliArgs),
 
@@ -58,11 +117,80 @@ pub enum CliCommand {
     #[command(about = "Initializes the dotfiles repository", bin_name = "twist")]
     Init(InitCliArgs),
 
+    #[command(
+        about = "Turns an existing dotfiles repository into the Twist repository",
+        name = "adopt"
+    )]
+    Adopt(AdoptCliArgs),
+
+    #[command(
+        about = "Imports the dotfiles managed by another dotfile manager",
+        name = "import"
+    )]
+    Import(ImportCliArgs),
+
+    #[command(about = "Exports a profile for systems without Twist", name = "export")]
+    Export(ExportCliArgs),
+
     #[command(about = "Pulls the dotfiles from the remote repository", name = "pull")]
     PullFromRemote(PullFromRemoteCliArgs),
 
     #[command(about = "Pushes the dotfiles to the remote repository", name = "push")]
     PushToRemote(PushToRemoteCliArgs),
+
+    #[command(
+        about = "Moves the root directory to the XDG data home or the given location",
+        name = "migrate-root"
+    )]
+    MigrateRoot(MigrateRootCliArgs),
+
+    #[command(
+        about = "Moves the tracked files to match the given repository layout",
+        name = "relayout"
+    )]
+    Relayout(RelayoutCliArgs),
+
+    #[command(
+        about = "Shows how the given path is tracked in the dotfiles repository",
+        name = "which"
+    )]
+    Which(WhichCliArgs),
+
+    #[command(
+        about = "Checks the dotfiles repository and its configuration for problems",
+        name = "doctor"
+    )]
+    Doctor(DoctorCliArgs),
+
+    #[command(
+        about = "Removes files that no entry refers to and runs git maintenance",
+        name = "gc"
+    )]
+    Gc(GcCliArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct GcCliArgs {
+    #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_GC)]
+    pub message: String,
+
+    #[arg(long, short = 'y', help = "Remove the orphaned files without asking")]
+    pub yes: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DoctorCliArgs {
+    #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_DOCTOR)]
+    pub message: String,
+
+    #[arg(long, help = "Repair the problems that can be fixed safely")]
+    pub fix: bool,
+
+    #[arg(
+        long,
+        help = "Restore the configuration file that fails to load from the last committed version"
+    )]
+    pub fix_config: bool,
 }
 
 #[derive(Debug, Args)]
@@ -79,15 +207,284 @@ pub struct AddFilesCliArgs {
     #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_ADD)]
     pub message: String,
 
-    #[arg(required = true)]
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = CliOverlapPolicy::Refuse,
+        help = "How to handle paths inside of, or containing, tracked entries"
+    )]
+    pub on_overlap: CliOverlapPolicy,
+
+    #[arg(
+        long,
+        help = "Write new entries to this configuration file, relative to the root directory"
+    )]
+    pub config_file: Option<PathBuf>,
+
+    #[arg(
+        long = "app",
+        value_name = "APP",
+        help = "Add the files of an application preset, tagged with the application name"
+    )]
+    pub apps: Vec<String>,
+
+    #[arg(required_unless_present = "apps")]
     pub paths: Vec<PathBuf>,
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CliOverlapPolicy {
+    #[value(help = "Fails without changing anything")]
+    Refuse,
+    #[value(help = "Keeps only the outermost directory entry")]
+    Merge,
+    #[value(help = "Tracks the inner entry separately from the outer directory")]
+    Override,
+}
+
+#[derive(Debug, Args)]
+pub struct MoveEntryCliArgs {
+    #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_MOVE)]
+    pub message: String,
+
+    #[arg(
+        long,
+        help = "Only move the repository copy, the system file was already moved"
+    )]
+    pub repo_only: bool,
+
+    pub old_path: PathBuf,
+
+    pub new_path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct EntryCliArgs {
+    #[command(subcommand)]
+    pub command: EntryCliCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum EntryCliCommand {
+    #[command(about = "Shows the options of an entry", name = "show")]
+    Show { path: PathBuf },
+
+    #[command(about = "Sets options of an entry", name = "set")]
+    Set {
+        #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_ENTRY)]
+        message: String,
+
+        path: PathBuf,
+
+        #[arg(
+            required = true,
+            value_name = "KEY=VALUE",
+            help = "The options to set: mode, excludes, tags, conditions, template, encryption or validate, lists are separated by commas. validate takes toml, json, yaml, ini, sh, none to skip the check, or a command that gets the path appended"
+        )]
+        options: Vec<String>,
+    },
+
+    #[command(about = "Removes options from an entry", name = "unset")]
+    Unset {
+        #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_ENTRY)]
+        message: String,
+
+        path: PathBuf,
+
+        #[arg(required = true, value_name = "KEY")]
+        keys: Vec<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigCliArgs {
+    #[command(subcommand)]
+    pub command: ConfigCliCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCliCommand {
+    #[command(about = "Prints the value of a setting", name = "get")]
+    Get { key: String },
+
+    #[command(about = "Changes a setting", name = "set")]
+    Set {
+        #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_SETTINGS)]
+        message: String,
+
+        #[arg(
+            help = "The setting to change: committer-name, committer-email, default-profile, default-remote (only read by init --remote) or link-mode (stored for apply, which is not implemented yet)"
+        )]
+        key: String,
+
+        value: String,
+    },
+
+    #[command(about = "Removes a setting", name = "unset")]
+    Unset {
+        #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_SETTINGS)]
+        message: String,
+
+        key: String,
+    },
+
+    #[command(about = "Lists all settings", name = "list")]
+    List,
+
+    #[command(
+        about = "Converts the configuration file to another format",
+        name = "convert"
+    )]
+    Convert {
+        #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_CONVERT)]
+        message: String,
+
+        #[arg(long, value_enum)]
+        to: CliConfigFormat,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CliConfigFormat {
+    #[value(help = "Stores the configuration in config.toml")]
+    Toml,
+    #[value(help = "Stores the configuration in config.yaml")]
+    Yaml,
+    #[value(help = "Stores the configuration in config.json")]
+    Json,
+}
+
+#[derive(Debug, Args)]
+pub struct DiscoverCliArgs {
+    #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_DISCOVER)]
+    pub message: String,
+
+    #[arg(long, help = "Only list the dotfiles, without offering to add them")]
+    pub list: bool,
+
+    #[arg(
+        long,
+        short = 'y',
+        conflicts_with = "list",
+        help = "Add every dotfile found without asking"
+    )]
+    pub yes: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ListEntriesCliArgs {}
+
+#[derive(Debug, Args)]
+pub struct MergeConfigCliArgs {
+    pub base: PathBuf,
+
+    pub ours: PathBuf,
+
+    pub theirs: PathBuf,
+
+    pub path: Option<PathBuf>,
+}
+
 #[derive(Debug, Args)]
 pub struct ApplyFilesCliArgs {}
 
 #[derive(Debug, Args)]
-pub struct InitCliArgs {}
+pub struct InitCliArgs {
+    #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_INIT)]
+    pub message: String,
+
+    #[arg(
+        long,
+        help = "Create the repository with this profile and make it the default"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(long, help = "Add this URL as the remote to push and pull")]
+    pub remote: Option<String>,
+
+    #[arg(
+        long,
+        help = "Clone an existing dotfiles repository from this path or URL"
+    )]
+    pub from: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct AdoptCliArgs {
+    #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_ADOPT)]
+    pub message: String,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "default",
+        help = "The layout the files in the repository follow"
+    )]
+    pub layout: CliRepoLayout,
+
+    #[arg(
+        long,
+        short = 'y',
+        help = "Resolve ambiguous paths without asking, preferring hidden ones"
+    )]
+    pub yes: bool,
+
+    #[arg(help = "The path or URL of the repository")]
+    pub repo: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportCliArgs {
+    #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_IMPORT)]
+    pub message: String,
+
+    #[arg(long, value_enum, help = "The dotfile manager to import from")]
+    pub from: CliImportSource,
+
+    #[arg(help = "The configuration, source directory or repository of the dotfile manager")]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CliImportSource {
+    #[value(help = "Reads the dotfiles listed in a config.yaml")]
+    Dotdrop,
+    #[value(help = "Reads a chezmoi source directory")]
+    Chezmoi,
+    #[value(help = "Reads a directory of GNU Stow packages")]
+    Stow,
+    #[value(help = "Reads the files committed to a yadm repository")]
+    Yadm,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportCliArgs {
+    #[arg(long, help = "The profile to export, defaults to the current one")]
+    pub profile: Option<String>,
+
+    #[arg(long, value_enum, default_value = "tar")]
+    pub format: CliExportFormat,
+
+    #[arg(long, short, help = "Where to write the export")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CliExportFormat {
+    #[value(help = "Writes a GNU Stow package named after the profile")]
+    Stow,
+    #[value(help = "Writes a tarball with a POSIX install.sh")]
+    Tar,
+}
+
+#[derive(Debug, Args)]
+pub struct MigrateRootCliArgs {
+    #[arg(
+        long,
+        help = "Move the root directory here instead of the XDG data home"
+    )]
+    pub to: Option<PathBuf>,
+}
 
 #[derive(Debug, Args)]
 pub struct PullFromRemoteCliArgs {}
@@ -95,6 +492,27 @@ pub struct PullFromRemoteCliArgs {}
 #[derive(Debug, Args)]
 pub struct PushToRemoteCliArgs {}
 
+#[derive(Debug, Args)]
+pub struct RelayoutCliArgs {
+    #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_RELAYOUT)]
+    pub message: String,
+
+    #[arg(value_enum)]
+    pub layout: CliRepoLayout,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CliRepoLayout {
+    #[value(help = "Maps ~ to home/ and strips leading dots")]
+    Default,
+    #[value(help = "Maps ~ to home/ and keeps leading dots")]
+    KeepDots,
+    #[value(help = "Maps ~ to home/ and replaces leading dots with dot_")]
+    Chezmoi,
+    #[value(help = "Groups the default layout by application name")]
+    ByApp,
+}
+
 #[derive(Debug, Args)]
 pub struct RemoveFilesCliArgs {
     #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_REMOVE)]
@@ -104,6 +522,11 @@ pub struct RemoveFilesCliArgs {
     pub paths: Vec<PathBuf>,
 }
 
+#[derive(Debug, Args)]
+pub struct WhichCliArgs {
+    pub path: PathBuf,
+}
+
 #[derive(Debug, Args)]
 pub struct UpdateRepositoryCliArgs {
     #[arg(long, short = 'm', default_value = DEFAULT_COMMIT_MESSAGE_FOR_UPDATE)]
@@ -1,19 +1,114 @@
+use std::fs::symlink_metadata;
 use std::path::PathBuf;
 
 use anyhow::Result;
+use log::warn;
 
 use super::Context;
+use crate::config::{EntryOptions, OverlapPolicy};
+use crate::files::preset::Preset;
 
 #[derive(Debug)]
 pub struct AddFilesArgs {
     pub message: String,
     pub paths: Vec<PathBuf>,
+    pub apps: Vec<String>,
+    pub overlap_policy: OverlapPolicy,
+    pub config_file: Option<PathBuf>,
 }
 
 pub fn add_files(args: AddFilesArgs, context: Context) -> Result<()> {
-    context.file_manager.add(&args.paths)?;
-    context.config.save()?;
-    context.repo.commit(&args.message)?;
+    if let Some(config_file) = &args.config_file {
+        context.config.set_target_file(config_file)?;
+    }
 
-    Ok(())
+    let presets = args
+        .apps
+        .iter()
+        .map(|app| Ok((app, Preset::load(context.paths.root_dir(), app)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    context.transaction(|| {
+        context.file_manager.add(&args.paths, args.overlap_policy)?;
+
+        for (app, preset) in &presets {
+            let paths: Vec<PathBuf> = preset
+                .paths
+                .iter()
+                .map(|p| context.paths.full_src_path(p))
+                .filter(|p| symlink_metadata(p).is_ok())
+                .collect();
+
+            if paths.is_empty() {
+                warn!("none of the paths of the {} preset exist", app);
+                continue;
+            }
+
+            let options = EntryOptions {
+                tags: vec![app.to_string()],
+                excludes: preset.excludes.clone(),
+                ..Default::default()
+            };
+            context
+                .file_manager
+                .add_with_options(&paths, args.overlap_policy, &options)?;
+        }
+
+        context.config.save()?;
+        context.commit(&args.message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::{commit_count, test_context};
+
+    fn args(paths: Vec<PathBuf>) -> AddFilesArgs {
+        AddFilesArgs {
+            message: "add".to_string(),
+            paths,
+            apps: vec![],
+            overlap_policy: OverlapPolicy::Refuse,
+            config_file: None,
+        }
+    }
+
+    #[test]
+    fn test_add_validates_files() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let home_dir = context.paths.home_dir.clone();
+        let root_dir = context.paths.root_dir.clone();
+        create_dir_all(home_dir.join(".config/Code/User")).unwrap();
+        let settings_path = home_dir.join(".config/Code/User/settings.json");
+        write(
+            &settings_path,
+            "{\n  // the theme\n  \"workbench.colorTheme\": \"Default Dark+\",\n}\n",
+        )
+        .unwrap();
+        let starship_path = home_dir.join(".config/starship.toml");
+        write(&starship_path, "[character\nsymbol = \">\"\n").unwrap();
+
+        add_files(args(vec![settings_path]), context).unwrap();
+        assert_eq!(commit_count(&root_dir), 1);
+
+        let context = test_context(dir.path());
+        assert!(add_files(args(vec![starship_path.clone()]), context).is_err());
+        assert_eq!(commit_count(&root_dir), 1);
+
+        let mut context = test_context(dir.path());
+        assert!(context
+            .config
+            .files()
+            .iter()
+            .all(|f| f.full_src_path != starship_path));
+        context.verify = false;
+        add_files(args(vec![starship_path]), context).unwrap();
+        assert_eq!(commit_count(&root_dir), 2);
+    }
 }
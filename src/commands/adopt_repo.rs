@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::fs::{metadata, read_dir, remove_dir, remove_dir_all, rename};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::{debug, info, warn};
+use thiserror::Error;
+
+use super::init_repo::clone_repository;
+use super::prompt::choose;
+use super::Context;
+use crate::files::git::GitRepository;
+use crate::files::layout::RepoLayout;
+use crate::files::path::{collect_files, Paths};
+
+#[derive(Debug)]
+pub struct AdoptRepoArgs {
+    pub message: String,
+    pub repo: String,
+    pub layout: RepoLayout,
+    pub profile: Option<String>,
+    pub yes: bool,
+    pub verify: bool,
+}
+
+#[derive(Error, Debug)]
+enum AdoptRepoError {
+    #[error("a repository already exists at {0}, adopt only works with a new root directory")]
+    AlreadyExists(PathBuf),
+    #[error("no files below home/ were found in {0}, check the --layout")]
+    NothingToAdopt(String),
+}
+
+pub fn adopt_repo(args: AdoptRepoArgs, paths: Paths) -> Result<()> {
+    let root_dir = paths.root_dir.clone();
+
+    if GitRepository::exists(&root_dir) {
+        return Err(AdoptRepoError::AlreadyExists(root_dir).into());
+    }
+
+    // cloning keeps the history, the files are then moved in a commit of their own
+    clone_repository(&args.repo, &root_dir, None)?;
+
+    // a half adopted clone would block the next attempt, so it is removed on failure
+    adopt_files(args, paths).inspect_err(|_| {
+        if let Err(err) = remove_dir_all(&root_dir) {
+            warn!("failed to remove the clone at {:?}: {}", root_dir, err);
+        }
+    })
+}
+
+fn adopt_files(args: AdoptRepoArgs, paths: Paths) -> Result<()> {
+    let mut context = Context::with_paths(paths, args.profile.as_deref())?;
+    context.verify = args.verify;
+    context.paths.set_layout(args.layout);
+
+    let root_dir = context.paths.root_dir.as_path();
+    let repo_files = collect_files(root_dir)?;
+
+    let mut decisions = BTreeMap::new();
+    let mut adopted = BTreeMap::new();
+
+    for repo_path in repo_files {
+        let src_paths = args.layout.src_paths(&repo_path);
+
+        let src_path = match src_paths.as_slice() {
+            [] => {
+                info!("{:?} is not below home/, leaving it in place", repo_path);
+                continue;
+            }
+            [src_path] => src_path.clone(),
+            _ => {
+                let index = resolve(
+                    &repo_path,
+                    &src_paths,
+                    &context.paths,
+                    &mut decisions,
+                    args.yes,
+                )?;
+                src_paths[index].clone()
+            }
+        };
+
+        if let Some(other) = adopted.insert(src_path.clone(), repo_path.clone()) {
+            warn!(
+                "{:?} and {:?} both map to {:?}, keeping the latter",
+                other, repo_path, src_path
+            );
+        }
+    }
+
+    if adopted.is_empty() {
+        return Err(AdoptRepoError::NothingToAdopt(args.repo).into());
+    }
+
+    context.transaction(|| {
+        let mut files = vec![];
+
+        for (src_path, repo_path) in &adopted {
+            let old_path = root_dir.join(repo_path);
+            let new_path = context.paths.files_dir.join(repo_path);
+
+            debug!("moving {:?} to {:?}", old_path, new_path);
+            context.transaction.track(&old_path)?;
+            context.transaction.track(&new_path)?;
+            Paths::ensure_parent_dir(&new_path)?;
+            rename(&old_path, &new_path)?;
+            remove_empty_dirs(root_dir, &old_path)?;
+
+            files.push(
+                context
+                    .paths
+                    .resolve_file_paths_from_config_paths(src_path, repo_path),
+            );
+        }
+
+        context.config.set_layout(args.layout);
+        context.config.add_files(&files);
+        context.config.save()?;
+        context.commit(&args.message)
+    })?;
+
+    info!("adopted {} files from {}", adopted.len(), args.repo);
+
+    Ok(())
+}
+
+// the choice is made once per directory below home and reused for the files in it
+fn resolve(
+    repo_path: &Path,
+    src_paths: &[PathBuf],
+    paths: &Paths,
+    decisions: &mut BTreeMap<PathBuf, usize>,
+    yes: bool,
+) -> Result<usize> {
+    let prefixes: Vec<PathBuf> = src_paths
+        .iter()
+        .map(|p| p.components().take(2).collect())
+        .collect();
+
+    if let Some(index) = decisions.get(&prefixes[0]) {
+        return Ok(*index);
+    }
+
+    let existing: Vec<_> = prefixes
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| metadata(paths.full_src_path(p)).is_ok())
+        .map(|(i, _)| i)
+        .collect();
+
+    let index = match existing.as_slice() {
+        [index] => *index,
+        _ if yes => 0,
+        _ => {
+            let options: Vec<_> = prefixes.iter().map(|p| p.display().to_string()).collect();
+            choose(
+                &format!(
+                    "{} could belong to more than one path, which one is it?",
+                    repo_path.display()
+                ),
+                &options,
+            )?
+        }
+    };
+
+    decisions.insert(prefixes[0].clone(), index);
+
+    Ok(index)
+}
+
+fn remove_empty_dirs(root_dir: &Path, path: &Path) -> Result<()> {
+    let mut parent = path.parent();
+
+    while let Some(dir) = parent.filter(|p| p.starts_with(root_dir) && *p != root_dir) {
+        if read_dir(dir)?.next().is_some() {
+            break;
+        }
+
+        remove_dir(dir)?;
+        parent = dir.parent();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string};
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::{commit_count, commit_files};
+
+    fn args(repo: &Path) -> AdoptRepoArgs {
+        AdoptRepoArgs {
+            message: "adopt".to_string(),
+            repo: repo.to_str().unwrap().to_string(),
+            layout: RepoLayout::default(),
+            profile: None,
+            yes: true,
+            verify: true,
+        }
+    }
+
+    fn paths(dir: &Path) -> Paths {
+        let home_dir = dir.join("home");
+        create_dir_all(&home_dir).unwrap();
+        Paths::new_with_home_dir(dir.join("root"), home_dir)
+    }
+
+    #[test]
+    fn test_adopt_moves_home_files() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        commit_files(
+            &source_dir,
+            &[
+                ("home/zshrc", "export EDITOR=vim\n"),
+                ("README.md", "dotfiles\n"),
+            ],
+        );
+
+        adopt_repo(args(&source_dir), paths(dir.path())).unwrap();
+
+        let root_dir = dir.path().join("root");
+        assert_eq!(
+            read_to_string(root_dir.join("dotfiles/home/zshrc")).unwrap(),
+            "export EDITOR=vim\n"
+        );
+        assert!(!root_dir.join("home").exists());
+        assert!(root_dir.join("README.md").exists());
+        assert_eq!(commit_count(&root_dir), 2);
+
+        let context = Context::with_paths(paths(dir.path()), None).unwrap();
+        let src_paths: Vec<_> = context
+            .config
+            .files()
+            .into_iter()
+            .map(|f| f.src_path)
+            .collect();
+        assert_eq!(src_paths, vec![PathBuf::from("~/.zshrc")]);
+    }
+
+    #[test]
+    fn test_adopt_removes_clone_on_failure() {
+        let dir = tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        commit_files(&source_dir, &[("README.md", "dotfiles\n")]);
+
+        assert!(adopt_repo(args(&source_dir), paths(dir.path())).is_err());
+        assert!(!dir.path().join("root").exists());
+
+        let fixed_dir = dir.path().join("fixed");
+        commit_files(&fixed_dir, &[("home/zshrc", "export EDITOR=vim\n")]);
+        adopt_repo(args(&fixed_dir), paths(dir.path())).unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use anyhow::Result;
+use log::info;
+
+use super::prompt::confirm;
+use super::Context;
+use crate::config::OverlapPolicy;
+use crate::files::path::HOME_DIR_PREFIX;
+
+#[derive(Debug)]
+pub struct DiscoverFilesArgs {
+    pub message: String,
+    pub list: bool,
+    pub yes: bool,
+}
+
+pub fn discover_files(args: DiscoverFilesArgs, context: Context) -> Result<()> {
+    context.config.ensure_loaded()?;
+
+    let discovered = context.file_manager.discover();
+
+    if discovered.is_empty() {
+        info!("no untracked dotfiles found");
+        return Ok(());
+    }
+
+    let display = |path: &Path| match path.strip_prefix(&context.paths.home_dir) {
+        Ok(relative_path) => Path::new(HOME_DIR_PREFIX).join(relative_path),
+        Err(_) => path.to_path_buf(),
+    };
+
+    if args.list {
+        for (app, path) in &discovered {
+            println!("{} ({})", display(path).display(), app);
+        }
+        return Ok(());
+    }
+
+    let mut selected = vec![];
+    for (app, path) in discovered {
+        if args.yes || confirm(&format!("track {} ({})?", display(&path).display(), app))? {
+            selected.push(path);
+        }
+    }
+
+    if selected.is_empty() {
+        info!("nothing to add");
+        return Ok(());
+    }
+
+    context.transaction(|| {
+        context.file_manager.add(&selected, OverlapPolicy::Refuse)?;
+        context.config.save()?;
+        context.commit(&args.message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+    use std::path::PathBuf;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::{commit_count, test_context};
+
+    #[test]
+    fn test_discover_skips_tracked_paths() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let home_dir = context.paths.home_dir.clone();
+        create_dir_all(home_dir.join(".config/git")).unwrap();
+        write(home_dir.join(".config/git/config"), "[user]\n").unwrap();
+        write(home_dir.join(".zshrc"), "export EDITOR=vim\n").unwrap();
+        write(home_dir.join(".vimrc"), "set number\n").unwrap();
+
+        context
+            .transaction(|| {
+                let paths = [home_dir.join(".zshrc"), home_dir.join(".config")];
+                context.file_manager.add(&paths, OverlapPolicy::Refuse)?;
+                context.config.save()?;
+                context.commit("add")
+            })
+            .unwrap();
+
+        let args = DiscoverFilesArgs {
+            message: "discover".to_string(),
+            list: false,
+            yes: true,
+        };
+        discover_files(args, context).unwrap();
+
+        let context = test_context(dir.path());
+        let src_paths: Vec<_> = context
+            .config
+            .files()
+            .into_iter()
+            .map(|f| f.src_path)
+            .collect();
+        assert_eq!(
+            src_paths,
+            vec![PathBuf::from("~/.vimrc"), PathBuf::from("~/.zshrc")]
+        );
+        assert_eq!(context.config.dirs().len(), 1);
+        assert_eq!(commit_count(&context.paths.root_dir), 2);
+    }
+}
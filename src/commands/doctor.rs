@@ -0,0 +1,357 @@
+use std::fmt::{self, Display, Formatter};
+use std::fs::{copy, metadata, write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::{info, warn};
+use thiserror::Error;
+
+use super::Context;
+use crate::files::path::{DirPathInfo, FilePathInfo, Paths, GIT_DIR_NAME};
+
+const CONFIG_BACKUP_DIR_NAME: &str = "twist-config-backup";
+
+#[derive(Debug)]
+pub struct DoctorArgs {
+    pub message: String,
+    pub fix: bool,
+    pub fix_config: bool,
+}
+
+#[derive(Error, Debug)]
+enum DoctorError {
+    #[error("there is no committed version of {0} to restore")]
+    NoCommittedConfig(PathBuf),
+    #[error("{0} problems remain")]
+    ProblemsFound(usize),
+}
+
+#[derive(Debug)]
+enum Problem {
+    NotTheWorkTree(PathBuf),
+    NestedRepository(PathBuf),
+    InvalidConfig,
+    MissingFromRepo(Vec<FilePathInfo>, Vec<DirPathInfo>),
+    Orphans(Vec<PathBuf>),
+    DefaultCommitter,
+    GitignoreNotCommitted,
+    MergeDriverMissing,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::NotTheWorkTree(root_dir) => {
+                write!(f, "{:?} is not the work tree of its repository", root_dir)
+            }
+            Problem::NestedRepository(path) => write!(f, "{:?} is a separate repository", path),
+            Problem::InvalidConfig => write!(f, "the configuration could not be loaded"),
+            Problem::MissingFromRepo(files, dirs) => {
+                let paths: Vec<_> = files
+                    .iter()
+                    .map(|f| f.src_path.display().to_string())
+                    .chain(dirs.iter().map(|d| format!("{}/", d.src_path.display())))
+                    .collect();
+                write!(f, "entries without a repository copy: {}", paths.join(", "))
+            }
+            Problem::Orphans(paths) => {
+                let paths: Vec<_> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "repository files without an entry: {}", paths.join(", "))
+            }
+            Problem::DefaultCommitter => write!(f, "the committer is the default placeholder"),
+            Problem::GitignoreNotCommitted => write!(f, "the .gitignore is not committed"),
+            Problem::MergeDriverMissing => {
+                write!(f, "the config merge driver is not registered")
+            }
+        }
+    }
+}
+
+impl Problem {
+    fn explanation(&self) -> &'static str {
+        match self {
+            Problem::NotTheWorkTree(_) => {
+                "twist commits the root directory, so it has to be the work tree of a non-bare repository"
+            }
+            Problem::NestedRepository(_) => {
+                "files inside a nested repository are not committed to the twist repository, move its history out and remove its .git directory"
+            }
+            Problem::InvalidConfig => {
+                "no command can work with the entries until the configuration parses, --fix restores the last committed version"
+            }
+            Problem::MissingFromRepo(_, _) => {
+                "these entries cannot be applied, --fix copies them from the system where they still exist"
+            }
+            Problem::Orphans(_) => {
+                "these files are left over from removed or moved entries and are never applied, remove them with `twist gc`"
+            }
+            Problem::DefaultCommitter => {
+                "commits are attributed to a placeholder, set committer-name and committer-email with `twist config set` or configure git"
+            }
+            Problem::GitignoreNotCommitted => {
+                "machine specific files could be committed on other machines, --fix commits it"
+            }
+            Problem::MergeDriverMissing => {
+                "git merges the configuration line by line instead of by entry, --fix registers `twist merge-config` for this clone"
+            }
+        }
+    }
+}
+
+pub fn doctor(args: DoctorArgs, context: Context) -> Result<()> {
+    if args.fix_config {
+        return fix_config(&context);
+    }
+
+    let problems = diagnose(&context)?;
+
+    if problems.is_empty() {
+        info!("no problems found");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        warn!("{}", problem);
+        info!("{}", problem.explanation());
+    }
+
+    let remaining = if args.fix {
+        fix(problems, &args.message, &context)?
+    } else {
+        problems.len()
+    };
+
+    if remaining > 0 {
+        return Err(DoctorError::ProblemsFound(remaining).into());
+    }
+
+    info!("all problems were fixed");
+
+    Ok(())
+}
+
+fn diagnose(context: &Context) -> Result<Vec<Problem>> {
+    let mut problems = vec![];
+    let root_dir = context.paths.root_dir();
+
+    let is_work_tree = context
+        .repo
+        .work_dir()
+        .is_some_and(|w| same_path(w, root_dir));
+    if !is_work_tree {
+        problems.push(Problem::NotTheWorkTree(root_dir.to_path_buf()));
+    }
+
+    if metadata(context.paths.files_dir.join(GIT_DIR_NAME)).is_ok() {
+        problems.push(Problem::NestedRepository(context.paths.files_dir.clone()));
+    }
+
+    // the entries cannot be checked against an invalid configuration
+    match context.config.ensure_loaded() {
+        Ok(()) => {
+            let (files, dirs) = context.file_manager.missing_from_repo();
+            if !files.is_empty() || !dirs.is_empty() {
+                problems.push(Problem::MissingFromRepo(files, dirs));
+            }
+
+            let orphans: Vec<_> = context
+                .file_manager
+                .orphans()?
+                .iter()
+                .filter_map(|p| p.strip_prefix(root_dir).ok())
+                .map(Path::to_path_buf)
+                .collect();
+            if !orphans.is_empty() {
+                problems.push(Problem::Orphans(orphans));
+            }
+        }
+        Err(_) => problems.push(Problem::InvalidConfig),
+    }
+
+    if context.repo.has_default_committer() {
+        problems.push(Problem::DefaultCommitter);
+    }
+
+    if !context.repo.has_committed_gitignore()? {
+        problems.push(Problem::GitignoreNotCommitted);
+    }
+
+    if !context
+        .repo
+        .has_merge_driver(&context.config.merge_patterns())
+    {
+        problems.push(Problem::MergeDriverMissing);
+    }
+
+    Ok(problems)
+}
+
+fn fix(problems: Vec<Problem>, message: &str, context: &Context) -> Result<usize> {
+    let mut remaining = 0;
+    let mut repo_problems = vec![];
+
+    for problem in problems {
+        match problem {
+            Problem::InvalidConfig => fix_config(context)?,
+            Problem::MergeDriverMissing => {
+                context
+                    .repo
+                    .register_merge_driver(&context.config.merge_patterns())?;
+                info!("registered the config merge driver");
+            }
+            Problem::MissingFromRepo(_, _) | Problem::GitignoreNotCommitted => {
+                repo_problems.push(problem)
+            }
+            _ => remaining += 1,
+        }
+    }
+
+    if repo_problems.is_empty() {
+        return Ok(remaining);
+    }
+
+    // a restored configuration is only loaded by the next run
+    if context.config.ensure_loaded().is_err() {
+        info!("run `twist doctor --fix` again to fix the remaining problems");
+        return Ok(remaining + repo_problems.len());
+    }
+
+    context.transaction(|| {
+        for problem in repo_problems {
+            if let Problem::MissingFromRepo(files, dirs) = problem {
+                remaining += copy_missing_to_repo(files, dirs, context)?;
+            }
+        }
+
+        context.commit(message)
+    })?;
+
+    Ok(remaining)
+}
+
+fn copy_missing_to_repo(
+    files: Vec<FilePathInfo>,
+    dirs: Vec<DirPathInfo>,
+    context: &Context,
+) -> Result<usize> {
+    let (files, missing_files): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|f| metadata(&f.full_src_path).is_ok());
+    let (dirs, missing_dirs): (Vec<_>, Vec<_>) = dirs
+        .into_iter()
+        .partition(|d| metadata(&d.full_src_path).is_ok());
+
+    context.file_manager.copy_to_repo(&files, &dirs)?;
+
+    for path in files.iter().map(|f| &f.src_path) {
+        info!("copied {:?} to the repository", path);
+    }
+    for path in dirs.iter().map(|d| &d.src_path) {
+        info!("copied {:?} to the repository", path);
+    }
+
+    if missing_files.is_empty() && missing_dirs.is_empty() {
+        return Ok(0);
+    }
+
+    warn!("{}", Problem::MissingFromRepo(missing_files, missing_dirs));
+    info!("they are missing from the system too, remove them with `twist rm`");
+    Ok(1)
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// restores the file that failed to load, which may be a fragment or an include,
+// and keeps the uncommitted version in the git directory where it is never committed
+fn fix_config(context: &Context) -> Result<()> {
+    let config_file_path = context
+        .config
+        .invalid_file()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| context.config.file_path());
+    let root_dir = context.paths.root_dir();
+    let relative_path = config_file_path.strip_prefix(root_dir)?;
+
+    let Some(content) = context.repo.read_committed_file(relative_path)? else {
+        return Err(DoctorError::NoCommittedConfig(config_file_path).into());
+    };
+
+    if metadata(&config_file_path).is_ok() {
+        let backup_path = root_dir
+            .join(GIT_DIR_NAME)
+            .join(CONFIG_BACKUP_DIR_NAME)
+            .join(relative_path);
+        Paths::ensure_parent_dir(&backup_path)?;
+        copy(&config_file_path, &backup_path)?;
+        info!(
+            "saved the current version of {:?} to {:?}",
+            relative_path, backup_path
+        );
+    }
+
+    write(&config_file_path, content)?;
+    info!(
+        "restored {:?} from the last committed version",
+        config_file_path
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string};
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::test_context;
+    use crate::config::OverlapPolicy;
+
+    #[test]
+    fn test_fix_config_restores_the_invalid_file() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let root_dir = context.paths.root_dir.clone();
+        let fragment_path = root_dir.join("config.d/work.toml");
+        write(context.paths.home_dir.join(".zshrc"), "export EDITOR=vim\n").unwrap();
+        create_dir_all(fragment_path.parent().unwrap()).unwrap();
+        write(&fragment_path, "version = 1\n").unwrap();
+
+        context
+            .transaction(|| {
+                let zshrc_path = context.paths.home_dir.join(".zshrc");
+                context
+                    .file_manager
+                    .add(&[zshrc_path], OverlapPolicy::Refuse)?;
+                context.config.save()?;
+                context.commit("add")
+            })
+            .unwrap();
+
+        let config_path = context.config.file_path();
+        let config = format!("{}# a local edit\n", read_to_string(&config_path).unwrap());
+        write(&config_path, &config).unwrap();
+        write(&fragment_path, "[files\n").unwrap();
+
+        let context = test_context(dir.path());
+        assert_eq!(context.config.invalid_file(), Some(fragment_path.as_path()));
+        let args = DoctorArgs {
+            message: "doctor".to_string(),
+            fix: false,
+            fix_config: true,
+        };
+        doctor(args, context).unwrap();
+
+        assert_eq!(read_to_string(&fragment_path).unwrap(), "version = 1\n");
+        assert_eq!(read_to_string(&config_path).unwrap(), config);
+        let backup_path = root_dir.join(".git/twist-config-backup/config.d/work.toml");
+        assert_eq!(read_to_string(backup_path).unwrap(), "[files\n");
+        assert!(test_context(dir.path()).config.ensure_loaded().is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use thiserror::Error;
+
+use super::Context;
+
+#[derive(Debug)]
+pub enum EntryOptionsArgs {
+    Show {
+        path: PathBuf,
+    },
+    Set {
+        message: String,
+        path: PathBuf,
+        options: Vec<String>,
+    },
+    Unset {
+        message: String,
+        path: PathBuf,
+        keys: Vec<String>,
+    },
+}
+
+#[derive(Error, Debug)]
+enum EntryOptionsCommandError {
+    #[error("expected an option in the form key=value, got {0}")]
+    InvalidAssignment(String),
+}
+
+pub fn entry_options(args: EntryOptionsArgs, context: Context) -> Result<()> {
+    match args {
+        EntryOptionsArgs::Show { path } => {
+            let src_path = context.file_manager.match_entry(&path)?;
+            let options = context.config.entry_options(&src_path).unwrap_or_default();
+
+            println!("{}", src_path.display());
+            print!("{}", toml::to_string(&options)?);
+
+            Ok(())
+        }
+        EntryOptionsArgs::Set {
+            message,
+            path,
+            options,
+        } => context.transaction(|| {
+            let src_path = context.file_manager.match_entry(&path)?;
+            let mut entry_options = context.config.entry_options(&src_path).unwrap_or_default();
+
+            for option in &options {
+                let (key, value) = option
+                    .split_once('=')
+                    .ok_or_else(|| EntryOptionsCommandError::InvalidAssignment(option.clone()))?;
+                entry_options.set(key.trim(), value.trim())?;
+            }
+
+            context.config.set_entry_options(&src_path, entry_options)?;
+            context.config.save()?;
+            context.commit(&message)
+        }),
+        EntryOptionsArgs::Unset {
+            message,
+            path,
+            keys,
+        } => context.transaction(|| {
+            let src_path = context.file_manager.match_entry(&path)?;
+            let mut entry_options = context.config.entry_options(&src_path).unwrap_or_default();
+
+            for key in &keys {
+                entry_options.unset(key)?;
+            }
+
+            context.config.set_entry_options(&src_path, entry_options)?;
+            context.config.save()?;
+            context.commit(&message)
+        }),
+    }
+}
@@ -3,7 +3,7 @@ use log::{debug, warn};
 use std::{
     env,
     ffi::{OsStr, OsString},
-    path::Path,
+    path::{Path, PathBuf},
     vec::IntoIter,
 };
 use subprocess::Exec;
@@ -33,11 +33,7 @@ const GIT_EXEC_PATH_ENV_VAR: &str = "GIT_EXEC_PATH";
 pub fn exec_git(args: ExecGitArgs, context: Context) -> Result<()> {
     let git_current_directory_flag = OsStr::new(GIT_CURRENT_DIRECTORY_FLAG);
 
-    let git_path = env::var(GIT_EXEC_PATH_ENV_VAR)
-        .ok()
-        .or_else(|| Some(String::default()))
-        .map(|p| Path::new(&p).join("git"))
-        .unwrap();
+    let git_path = git_path();
 
     let mut git_args = vec![
         git_current_directory_flag.to_os_string(),
@@ -59,6 +55,14 @@ pub fn exec_git(args: ExecGitArgs, context: Context) -> Result<()> {
     }
 }
 
+pub(super) fn git_path() -> PathBuf {
+    env::var(GIT_EXEC_PATH_ENV_VAR)
+        .ok()
+        .or_else(|| Some(String::default()))
+        .map(|p| Path::new(&p).join("git"))
+        .unwrap()
+}
+
 struct SafeExecGitArgs(IntoIter<OsString>);
 
 impl SafeExecGitArgs {
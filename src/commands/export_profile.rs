@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+use std::fs::{
+    copy, create_dir_all, metadata, read_dir, read_link, read_to_string, remove_dir_all,
+    set_permissions, symlink_metadata, write,
+};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{absolute, Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::Result;
+use glob::Pattern;
+use log::{debug, info, warn};
+use subprocess::Exec;
+use thiserror::Error;
+
+use super::Context;
+use crate::config::{ConfigManager, EntryOptions};
+use crate::files::path::{Paths, GIT_DIR_NAME, HOME_DIR_PREFIX};
+use crate::files::template::{render, TemplateError};
+
+const EXPORT_STAGING_DIR_NAME: &str = "twist-export";
+const TREE_DIR_NAME: &str = "tree";
+const FILES_DIR_NAME: &str = "files";
+const INSTALL_SCRIPT_NAME: &str = "install.sh";
+const HOME_ARCHIVE_DIR_NAME: &str = "home";
+const ROOT_ARCHIVE_DIR_NAME: &str = "root";
+const TAR_EXTENSION: &str = "tar.gz";
+
+const INSTALL_SCRIPT_HEADER: &str = r#"#!/bin/sh
+# Restores the exported dotfiles to their original locations.
+set -eu
+
+cd "$(dirname "$0")"
+
+install_entry() {
+    mkdir -p "$(dirname "$2")"
+    if [ -d "$1" ]; then
+        mkdir -p "$2"
+        cp -R "$1/." "$2"
+    else
+        cp "$1" "$2"
+    fi
+    if [ -n "$3" ]; then
+        chmod "$3" "$2"
+    fi
+    echo "installed $2"
+}
+
+"#;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Stow,
+    Tar,
+}
+
+#[derive(Debug)]
+pub struct ExportProfileArgs {
+    pub profile: Option<String>,
+    pub format: ExportFormat,
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Error, Debug)]
+enum ExportProfileError {
+    #[error("{0} already exists")]
+    OutputExists(PathBuf),
+    #[error("failed to create the archive")]
+    ArchiveFailed,
+    #[error("failed to exec tar: {0}")]
+    TarExecError(anyhow::Error),
+    #[error("failed to render {0}: {1}")]
+    Template(PathBuf, TemplateError),
+}
+
+struct ExportedEntry {
+    src_path: PathBuf,
+    full_repo_path: PathBuf,
+    options: EntryOptions,
+}
+
+pub fn export_profile(args: ExportProfileArgs, context: Context) -> Result<()> {
+    let profile = args
+        .profile
+        .clone()
+        .unwrap_or_else(|| context.repo.selected_profile().to_string());
+
+    let staging_dir = context
+        .paths
+        .root_dir()
+        .join(GIT_DIR_NAME)
+        .join(EXPORT_STAGING_DIR_NAME);
+    if metadata(&staging_dir).is_ok() {
+        remove_dir_all(&staging_dir)?;
+    }
+
+    let result = export(&args, &profile, &staging_dir, &context);
+    remove_dir_all(&staging_dir).unwrap_or_default();
+
+    result
+}
+
+fn export(
+    args: &ExportProfileArgs,
+    profile: &str,
+    staging_dir: &Path,
+    context: &Context,
+) -> Result<()> {
+    // the profile may not be checked out, so its committed tree is exported
+    let tree_dir = staging_dir.join(TREE_DIR_NAME);
+    context.repo.extract_profile(profile, &tree_dir)?;
+
+    let paths = Rc::new(Paths::new(&tree_dir));
+    let config = ConfigManager::open(&paths);
+    paths.set_layout(config.layout());
+    config.ensure_loaded()?;
+
+    let variables = config.variables();
+    let options = |src_path: &Path| config.entry_options(src_path).unwrap_or_default();
+    let entries: Vec<_> = config
+        .files()
+        .into_iter()
+        .map(|f| ExportedEntry {
+            options: options(&f.src_path),
+            src_path: f.src_path,
+            full_repo_path: f.full_repo_path,
+        })
+        .chain(config.dirs().into_iter().map(|d| ExportedEntry {
+            options: options(&d.src_path),
+            src_path: d.src_path,
+            full_repo_path: d.full_repo_path,
+        }))
+        .filter(|e| {
+            // encrypted files would be exported as ciphertext
+            if e.options.encryption.is_some() {
+                warn!("{:?} is encrypted, it was not exported", e.src_path);
+            }
+            e.options.encryption.is_none()
+        })
+        .collect();
+
+    let name = format!("twist-{}", profile);
+
+    match args.format {
+        ExportFormat::Stow => {
+            let output = absolute(args.output.clone().unwrap_or_else(|| PathBuf::from(&name)))?;
+            ensure_missing(&output)?;
+            if let Err(err) = export_stow(&entries, &output.join(profile), &variables) {
+                remove_dir_all(&output).unwrap_or_default();
+                return Err(err);
+            }
+            info!("exported {} entries to {:?}", entries.len(), output);
+        }
+        ExportFormat::Tar => {
+            let output = absolute(
+                args.output
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(format!("{}.{}", name, TAR_EXTENSION))),
+            )?;
+            ensure_missing(&output)?;
+            export_tar(&entries, staging_dir, &name, &output, &variables)?;
+            info!("exported {} entries to {:?}", entries.len(), output);
+        }
+    }
+
+    Ok(())
+}
+
+fn ensure_missing(output: &Path) -> Result<()> {
+    if metadata(output).is_ok() {
+        return Err(ExportProfileError::OutputExists(output.to_path_buf()).into());
+    }
+
+    Ok(())
+}
+
+// stow installs a package relative to the home directory
+fn export_stow(
+    entries: &[ExportedEntry],
+    package_dir: &Path,
+    variables: &BTreeMap<String, String>,
+) -> Result<()> {
+    create_dir_all(package_dir)?;
+
+    for entry in entries {
+        match entry.src_path.strip_prefix(HOME_DIR_PREFIX) {
+            Ok(relative_path) => write_entry(entry, &package_dir.join(relative_path), variables)?,
+            Err(_) => warn!(
+                "{:?} is outside of the home directory, stow cannot install it",
+                entry.src_path
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+fn export_tar(
+    entries: &[ExportedEntry],
+    staging_dir: &Path,
+    name: &str,
+    output: &Path,
+    variables: &BTreeMap<String, String>,
+) -> Result<()> {
+    let bundle_dir = staging_dir.join(name);
+    let mut script = INSTALL_SCRIPT_HEADER.to_string();
+
+    for entry in entries {
+        let (archive_path, target) = match entry.src_path.strip_prefix(HOME_DIR_PREFIX) {
+            Ok(relative_path) => (
+                Path::new(HOME_ARCHIVE_DIR_NAME).join(relative_path),
+                format!("\"$HOME\"/{}", quote(relative_path)),
+            ),
+            Err(_) => (
+                Path::new(ROOT_ARCHIVE_DIR_NAME).join(entry.src_path.strip_prefix("/")?),
+                quote(&entry.src_path),
+            ),
+        };
+        let archive_path = Path::new(FILES_DIR_NAME).join(archive_path);
+
+        write_entry(entry, &bundle_dir.join(&archive_path), variables)?;
+        script.push_str(&format!(
+            "install_entry {} {} '{}'\n",
+            quote(&archive_path),
+            target,
+            entry.options.mode.as_deref().unwrap_or_default()
+        ));
+    }
+
+    let script_path = bundle_dir.join(INSTALL_SCRIPT_NAME);
+    create_dir_all(&bundle_dir)?;
+    write(&script_path, script)?;
+    set_mode(&script_path, "755")?;
+
+    debug!("archiving {:?} to {:?}", bundle_dir, output);
+    let result = Exec::cmd("tar")
+        .arg("-czf")
+        .arg(output)
+        .arg("-C")
+        .arg(staging_dir)
+        .arg(name)
+        .join();
+
+    match result {
+        Ok(exit_status) if exit_status.success() => Ok(()),
+        Ok(_) => Err(ExportProfileError::ArchiveFailed.into()),
+        Err(err) => Err(ExportProfileError::TarExecError(err.into()).into()),
+    }
+}
+
+fn write_entry(
+    entry: &ExportedEntry,
+    dest: &Path,
+    variables: &BTreeMap<String, String>,
+) -> Result<()> {
+    let excludes = entry
+        .options
+        .excludes
+        .iter()
+        .map(|e| Pattern::new(e))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    copy_entry(
+        &entry.src_path,
+        &entry.full_repo_path,
+        &entry.full_repo_path,
+        dest,
+        &excludes,
+        entry.options.template.then_some(variables),
+    )?;
+
+    if let Some(mode) = &entry.options.mode {
+        set_mode(dest, mode)?;
+    }
+
+    Ok(())
+}
+
+fn copy_entry(
+    src_path: &Path,
+    base_dir: &Path,
+    src: &Path,
+    dest: &Path,
+    excludes: &[Pattern],
+    variables: Option<&BTreeMap<String, String>>,
+) -> Result<()> {
+    if symlink_metadata(src)?.is_symlink() {
+        Paths::ensure_parent_dir(dest)?;
+        symlink(read_link(src)?, dest)?;
+        return Ok(());
+    }
+
+    if metadata(src)?.is_dir() {
+        create_dir_all(dest)?;
+
+        for child in read_dir(src)? {
+            let path = child?.path();
+            let relative_path = path.strip_prefix(base_dir)?;
+            let is_excluded = excludes.iter().any(|pattern| {
+                pattern.matches_path(relative_path)
+                    || path
+                        .file_name()
+                        .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+            });
+
+            if !is_excluded {
+                let dest = dest.join(path.strip_prefix(src)?);
+                copy_entry(src_path, base_dir, &path, &dest, excludes, variables)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    Paths::ensure_parent_dir(dest)?;
+
+    match variables {
+        Some(variables) => {
+            let content = render(&read_to_string(src)?, variables).map_err(|err| {
+                let path = src_path.join(src.strip_prefix(base_dir).unwrap_or(src));
+                ExportProfileError::Template(path, err)
+            })?;
+            write(dest, content)?
+        }
+        None => {
+            copy(src, dest)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn set_mode(path: &Path, mode: &str) -> Result<()> {
+    let mode = u32::from_str_radix(mode, 8)?;
+    let mut permissions = metadata(path)?.permissions();
+    permissions.set_mode(mode);
+    set_permissions(path, permissions)?;
+
+    Ok(())
+}
+
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, set_permissions, Permissions};
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::test_context;
+    use crate::config::OverlapPolicy;
+
+    #[test]
+    fn test_export_tar() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let home_dir = context.paths.home_dir.clone();
+        create_dir_all(home_dir.join("bin")).unwrap();
+        write(home_dir.join(".zshrc"), "export EDITOR=vim\n").unwrap();
+        write(home_dir.join("bin/hello"), "#!/bin/sh\necho hello\n").unwrap();
+        set_permissions(home_dir.join("bin/hello"), Permissions::from_mode(0o755)).unwrap();
+        write(home_dir.join(".secret"), "ciphertext").unwrap();
+
+        context
+            .transaction(|| {
+                let paths = [".zshrc", "bin", ".secret"].map(|p| home_dir.join(p));
+                context.file_manager.add(&paths, OverlapPolicy::Refuse)?;
+                context.config.set_entry_options(
+                    Path::new("~/.secret"),
+                    EntryOptions {
+                        encryption: Some("age".to_string()),
+                        ..EntryOptions::default()
+                    },
+                )?;
+                context.config.save()?;
+                context.commit("add")
+            })
+            .unwrap();
+
+        let output = dir.path().join("export.tar.gz");
+        let args = ExportProfileArgs {
+            profile: None,
+            format: ExportFormat::Tar,
+            output: Some(output.clone()),
+        };
+        export_profile(args, context).unwrap();
+
+        let extract_dir = dir.path().join("extract");
+        create_dir_all(&extract_dir).unwrap();
+        let status = Exec::cmd("tar")
+            .arg("-xzf")
+            .arg(&output)
+            .arg("-C")
+            .arg(&extract_dir)
+            .join()
+            .unwrap();
+        assert!(status.success());
+
+        let bundle_dir = extract_dir.join("twist-main");
+        assert_eq!(
+            read_to_string(bundle_dir.join("files/home/.zshrc")).unwrap(),
+            "export EDITOR=vim\n"
+        );
+        let mode = metadata(bundle_dir.join("files/home/bin/hello"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert!(!bundle_dir.join("files/home/.secret").exists());
+
+        let script = read_to_string(bundle_dir.join(INSTALL_SCRIPT_NAME)).unwrap();
+        assert!(script.starts_with(INSTALL_SCRIPT_HEADER));
+        assert_eq!(
+            script[INSTALL_SCRIPT_HEADER.len()..]
+                .lines()
+                .collect::<Vec<_>>(),
+            vec![
+                "install_entry 'files/home/.zshrc' \"$HOME\"/'.zshrc' ''",
+                "install_entry 'files/home/bin' \"$HOME\"/'bin' ''",
+            ]
+        );
+    }
+}
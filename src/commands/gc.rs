@@ -0,0 +1,74 @@
+use anyhow::Result;
+use log::{debug, info};
+use subprocess::Exec;
+use thiserror::Error;
+
+use super::exec_git::git_path;
+use super::prompt::confirm;
+use super::Context;
+
+#[derive(Debug)]
+pub struct GcArgs {
+    pub message: String,
+    pub yes: bool,
+}
+
+#[derive(Error, Debug)]
+enum GcError {
+    #[error("git maintenance failed")]
+    MaintenanceFailed,
+    #[error("failed to exec git: {0}")]
+    GitExecError(anyhow::Error),
+}
+
+pub fn gc(args: GcArgs, context: Context) -> Result<()> {
+    context.config.ensure_loaded()?;
+
+    let orphans = context.file_manager.orphans()?;
+
+    if orphans.is_empty() {
+        info!("no orphaned files found");
+    } else {
+        println!("files that no entry refers to:");
+        for orphan in &orphans {
+            let path = orphan
+                .strip_prefix(context.paths.root_dir())
+                .unwrap_or(orphan);
+            println!("  {}", path.display());
+        }
+
+        if args.yes || confirm(&format!("remove {} files?", orphans.len()))? {
+            context.transaction(|| {
+                context.file_manager.remove_orphans(&orphans)?;
+                context.commit(&args.message)
+            })?;
+            info!("removed {} orphaned files", orphans.len());
+        } else {
+            info!("keeping the orphaned files");
+        }
+    }
+
+    maintain_repository(&context)
+}
+
+fn maintain_repository(context: &Context) -> Result<()> {
+    let root_dir = context.paths.root_dir();
+    debug!("running git maintenance in {:?}", root_dir);
+
+    // the default prune keeps unreachable objects for two weeks, so that an
+    // interrupted operation or the reflog can still be recovered
+    let result = Exec::cmd(git_path())
+        .arg("-C")
+        .arg(root_dir)
+        .args(&["gc", "--quiet"])
+        .join();
+
+    match result {
+        Ok(exit_status) if exit_status.success() => {
+            info!("packed the repository");
+            Ok(())
+        }
+        Ok(_) => Err(GcError::MaintenanceFailed.into()),
+        Err(err) => Err(GcError::GitExecError(err.into()).into()),
+    }
+}
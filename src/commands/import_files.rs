@@ -0,0 +1,171 @@
+use std::fs::{copy, metadata, write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use dircpy::copy_dir;
+use log::{info, warn};
+
+use super::Context;
+use crate::files::path::Paths;
+use crate::import::{Import, ImportSource, ImportedContent};
+
+#[derive(Debug)]
+pub struct ImportFilesArgs {
+    pub message: String,
+    pub source: ImportSource,
+    pub path: PathBuf,
+}
+
+pub fn import_files(args: ImportFilesArgs, context: Context) -> Result<()> {
+    context.config.ensure_loaded()?;
+
+    let import = args.source.read(&args.path)?;
+    let (imported, untranslated) = import_entries(import, &args.message, &context)?;
+
+    info!("imported {} entries from {}", imported, args.source.name());
+
+    if !untranslated.is_empty() {
+        warn!("{} items could not be translated:", untranslated.len());
+        for item in &untranslated {
+            println!("  {}", item);
+        }
+    }
+
+    Ok(())
+}
+
+// entries that are already tracked or would overwrite a file are reported with the untranslated items
+fn import_entries(
+    import: Import,
+    message: &str,
+    context: &Context,
+) -> Result<(usize, Vec<String>)> {
+    let mut untranslated = import.untranslated;
+    let mut imported = 0;
+
+    context.transaction(|| {
+        for entry in import.entries {
+            let src_path = &entry.src_path;
+
+            if context.config.entry_options(src_path).is_some() {
+                untranslated.push(format!(
+                    "{} is already tracked, it was not imported",
+                    src_path.display()
+                ));
+                continue;
+            }
+
+            let repo_path = context.paths.repo_path(src_path);
+            let full_repo_path = context.paths.files_dir.join(&repo_path);
+
+            if metadata(&full_repo_path).is_ok() {
+                untranslated.push(format!(
+                    "{} would overwrite {}, it was not imported",
+                    src_path.display(),
+                    full_repo_path.display()
+                ));
+                continue;
+            }
+
+            context.transaction.track(&full_repo_path)?;
+            Paths::ensure_parent_dir(&full_repo_path)?;
+
+            match entry.content {
+                ImportedContent::File(path) => {
+                    copy(path, &full_repo_path)?;
+                    let file = context
+                        .paths
+                        .resolve_file_paths_from_config_paths(src_path, &repo_path);
+                    context.config.add_files(&[file]);
+                }
+                ImportedContent::Blob(content) => {
+                    write(&full_repo_path, content)?;
+                    let file = context
+                        .paths
+                        .resolve_file_paths_from_config_paths(src_path, &repo_path);
+                    context.config.add_files(&[file]);
+                }
+                ImportedContent::Dir(path) => {
+                    copy_dir(path, &full_repo_path)?;
+                    let dir = context
+                        .paths
+                        .resolve_dir_paths_from_config_paths(src_path, &repo_path);
+                    context.config.add_dirs(&[dir]);
+                }
+            }
+
+            if !entry.options.is_empty() {
+                context.config.set_entry_options(src_path, entry.options)?;
+            }
+
+            imported += 1;
+        }
+
+        context.config.save()?;
+        context.commit(message)
+    })?;
+
+    Ok((imported, untranslated))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::test_context;
+    use crate::config::EntryOptions;
+    use crate::import::ImportedEntry;
+
+    fn entry(src_path: &str, content: &str) -> ImportedEntry {
+        ImportedEntry {
+            src_path: PathBuf::from(src_path),
+            content: ImportedContent::Blob(content.as_bytes().to_vec()),
+            options: EntryOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_import_reports_tracked_and_existing_files() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let full_repo_path = context
+            .paths
+            .files_dir
+            .join(context.paths.repo_path(Path::new("~/.vimrc")));
+        create_dir_all(full_repo_path.parent().unwrap()).unwrap();
+        write(&full_repo_path, "set number\n").unwrap();
+
+        let import = Import {
+            entries: vec![entry("~/.zshrc", "export EDITOR=vim\n")],
+            untranslated: vec![],
+        };
+        import_entries(import, "import", &context).unwrap();
+
+        let import = Import {
+            entries: vec![
+                entry("~/.zshrc", "export EDITOR=nano\n"),
+                entry("~/.vimrc", "set nonumber\n"),
+                entry("~/.bashrc", "export EDITOR=vim\n"),
+            ],
+            untranslated: vec!["the actions section was not translated".to_string()],
+        };
+        let (imported, untranslated) = import_entries(import, "import", &context).unwrap();
+
+        assert_eq!(imported, 1);
+        assert_eq!(
+            untranslated,
+            vec![
+                "the actions section was not translated".to_string(),
+                "~/.zshrc is already tracked, it was not imported".to_string(),
+                format!(
+                    "~/.vimrc would overwrite {}, it was not imported",
+                    full_repo_path.display()
+                ),
+            ]
+        );
+    }
+}
@@ -1,31 +1,183 @@
-use std::fs::create_dir_all;
+use std::fs::remove_dir_all;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use git2::Repository;
+use log::{debug, info, warn};
+use subprocess::Exec;
 use thiserror::Error;
 
+use super::exec_git::git_path;
 use super::Context;
+use crate::files::git::GitRepository;
+use crate::files::path::GIT_DIR_NAME;
+
+const DEFAULT_REMOTE_NAME: &str = "origin";
 
 #[derive(Debug)]
-pub struct InitRepoArgs {}
+pub struct InitRepoArgs {
+    pub message: String,
+    pub profile: Option<String>,
+    pub remote: Option<String>,
+    pub from: Option<String>,
+}
 
 #[derive(Error, Debug)]
-enum InitError {
-    #[error("unable to create repository directory")]
-    UnableToCreateDirectory,
+pub(super) enum InitError {
+    #[error("a repository already exists at {0}, it cannot be cloned over")]
+    AlreadyExists(PathBuf),
+    #[error("failed to clone {0}")]
+    CloneFailed(String),
+    #[error("failed to exec git: {0}")]
+    GitExecError(anyhow::Error),
+}
+
+pub fn init_repo(args: InitRepoArgs, root_dir: &Path) -> Result<()> {
+    let is_new = !GitRepository::exists(root_dir);
+
+    match (&args.from, is_new) {
+        (Some(from), true) => clone_repository(from, root_dir, args.profile.as_deref())?,
+        (Some(_), false) => return Err(InitError::AlreadyExists(root_dir.to_path_buf()).into()),
+        (None, false) => info!("a repository already exists at {:?}", root_dir),
+        (None, true) => info!("creating a repository at {:?}", root_dir),
+    }
+
+    let context = Context::new(root_dir, args.profile.as_deref())?;
+    remove_nested_repository(&context.paths.files_dir)?;
+
+    if let Some(url) = &args.remote {
+        let settings = context.config.settings();
+        let name = settings
+            .default_remote
+            .as_deref()
+            .unwrap_or(DEFAULT_REMOTE_NAME);
+
+        if context.repo.set_remote(name, url)? {
+            info!("set the remote {} to {}", name, url);
+        }
+    }
+
+    if !is_new || args.from.is_some() {
+        return Ok(());
+    }
+
+    context.transaction(|| {
+        if let Some(profile) = &args.profile {
+            let mut settings = context.config.settings();
+            settings.default_profile = Some(profile.clone());
+            context.config.set_settings(settings);
+            context.config.save()?;
+        }
+
+        context.commit(&args.message)
+    })
+}
+
+pub(super) fn clone_repository(from: &str, root_dir: &Path, profile: Option<&str>) -> Result<()> {
+    info!("cloning {} to {:?}", from, root_dir);
+
+    let mut exec = Exec::cmd(git_path()).arg("clone");
+    if let Some(profile) = profile {
+        exec = exec.args(&["--branch", profile]);
+    }
+
+    match exec.arg(from).arg(root_dir).join() {
+        Ok(exit_status) if exit_status.success() => Ok(()),
+        Ok(_) => Err(InitError::CloneFailed(from.to_string()).into()),
+        Err(err) => Err(InitError::GitExecError(err.into()).into()),
+    }
 }
 
-pub fn init_repo(_args: InitRepoArgs, context: Context) -> Result<()> {
-    let repo_dir = &context.paths.files_dir;
+// older versions of init created an empty repository inside of dotfiles/,
+// which hides the files from the repository in the root directory
+fn remove_nested_repository(files_dir: &Path) -> Result<()> {
+    let nested_repo = match Repository::open(files_dir) {
+        Ok(repo) if repo.path().starts_with(files_dir) => repo,
+        _ => return Ok(()),
+    };
 
-    create_dir_all(repo_dir).map_err(|_| InitError::UnableToCreateDirectory)?;
+    let mut revwalk = nested_repo.revwalk()?;
+    if revwalk.push_head().is_ok() {
+        for oid in revwalk {
+            let tree = nested_repo.find_commit(oid?)?.tree()?;
+            if !tree.is_empty() {
+                warn!(
+                    "{:?} contains a separate repository with history, move it out by hand",
+                    files_dir
+                );
+                return Ok(());
+            }
+        }
+    }
 
-    let sig = git2::Signature::new("Example", "Example", &git2::Time::new(0, 0))?;
-    let repo = Repository::open(repo_dir).or_else(|_| Repository::init(repo_dir))?;
-    let mut index = repo.index()?;
-    let tree_id = index.write_tree()?;
-    let tree = repo.find_tree(tree_id)?;
-    repo.commit(Some("HEAD"), &sig, &sig, "Initial Commit", &tree, &[])?;
+    debug!("removing the empty repository in {:?}", files_dir);
+    remove_dir_all(files_dir.join(GIT_DIR_NAME))?;
+    info!("removed the empty repository created in {:?}", files_dir);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::{commit_count, commit_files};
+
+    fn args() -> InitRepoArgs {
+        InitRepoArgs {
+            message: "init".to_string(),
+            profile: None,
+            remote: None,
+            from: None,
+        }
+    }
+
+    #[test]
+    fn test_init_is_idempotent() {
+        let dir = tempdir().unwrap();
+        let root_dir = dir.path().join("root");
+        let url = "https://example.com/dotfiles.git";
+
+        init_repo(args(), &root_dir).unwrap();
+        init_repo(
+            InitRepoArgs {
+                remote: Some(url.to_string()),
+                ..args()
+            },
+            &root_dir,
+        )
+        .unwrap();
+
+        assert_eq!(commit_count(&root_dir), 1);
+        let repo = Repository::open(&root_dir).unwrap();
+        assert_eq!(
+            repo.find_remote(DEFAULT_REMOTE_NAME).unwrap().url(),
+            Some(url)
+        );
+    }
+
+    #[test]
+    fn test_init_removes_empty_nested_repository() {
+        let dir = tempdir().unwrap();
+        let root_dir = dir.path().join("root");
+        Repository::init(root_dir.join("dotfiles")).unwrap();
+
+        init_repo(args(), &root_dir).unwrap();
+
+        assert!(!root_dir.join("dotfiles").join(GIT_DIR_NAME).exists());
+        assert_eq!(commit_count(&root_dir), 1);
+    }
+
+    #[test]
+    fn test_init_keeps_nested_repository_with_history() {
+        let dir = tempdir().unwrap();
+        let root_dir = dir.path().join("root");
+        let files_dir = root_dir.join("dotfiles");
+        commit_files(&files_dir, &[("zshrc", "export EDITOR=vim\n")]);
+
+        init_repo(args(), &root_dir).unwrap();
+
+        assert!(files_dir.join(GIT_DIR_NAME).exists());
+    }
+}
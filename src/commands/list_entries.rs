@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use super::Context;
+
+#[derive(Debug)]
+pub struct ListEntriesArgs {}
+
+pub fn list_entries(_args: ListEntriesArgs, context: Context) -> Result<()> {
+    let mut entries: Vec<(PathBuf, PathBuf, bool)> = context
+        .config
+        .files()
+        .into_iter()
+        .map(|f| (f.src_path, f.repo_path, false))
+        .chain(
+            context
+                .config
+                .dirs()
+                .into_iter()
+                .map(|d| (d.src_path, d.repo_path, true)),
+        )
+        .collect();
+    entries.sort();
+
+    for (src_path, repo_path, is_dir) in entries {
+        let suffix = if is_dir { "/" } else { "" };
+        let mut line = format!(
+            "{}{} -> {}{}",
+            src_path.display(),
+            suffix,
+            repo_path.display(),
+            suffix
+        );
+
+        if context.config.is_disabled(&src_path) {
+            line.push_str(" (disabled locally)");
+        }
+
+        if let Some(target) = context.config.local_target(&src_path) {
+            line.push_str(&format!(" (local target: {})", target.display()));
+        }
+
+        println!("{}", line);
+    }
+
+    let variables = context.config.variables();
+
+    if !variables.is_empty() {
+        println!();
+        println!("variables:");
+    }
+
+    for (name, value) in variables {
+        let marker = if context.config.is_local_variable(&name) {
+            " (set locally)"
+        } else {
+            ""
+        };
+        println!("  {} = {}{}", name, value, marker);
+    }
+
+    Ok(())
+}
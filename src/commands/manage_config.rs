@@ -0,0 +1,91 @@
+use anyhow::Result;
+use log::info;
+use thiserror::Error;
+
+use super::Context;
+use crate::config::{ConfigFormat, SETTING_KEYS};
+
+#[derive(Debug)]
+pub enum ManageConfigArgs {
+    Get {
+        key: String,
+    },
+    Set {
+        message: String,
+        key: String,
+        value: String,
+    },
+    Unset {
+        message: String,
+        key: String,
+    },
+    List,
+    Convert {
+        format: ConfigFormat,
+        message: String,
+    },
+}
+
+#[derive(Error, Debug)]
+enum ManageConfigError {
+    #[error("{0} is not set")]
+    NotSet(String),
+}
+
+pub fn manage_config(args: ManageConfigArgs, context: Context) -> Result<()> {
+    match args {
+        ManageConfigArgs::Get { key } => match context.config.settings().get(&key)? {
+            Some(value) => {
+                println!("{}", value);
+                Ok(())
+            }
+            None => Err(ManageConfigError::NotSet(key).into()),
+        },
+        ManageConfigArgs::Set {
+            message,
+            key,
+            value,
+        } => context.transaction(|| {
+            let mut settings = context.config.settings();
+            settings.set(&key, &value)?;
+            context.config.set_settings(settings);
+            context.config.save()?;
+            context.commit(&message)
+        }),
+        ManageConfigArgs::Unset { message, key } => context.transaction(|| {
+            let mut settings = context.config.settings();
+            settings.unset(&key)?;
+            context.config.set_settings(settings);
+            context.config.save()?;
+            context.commit(&message)
+        }),
+        ManageConfigArgs::List => {
+            let settings = context.config.settings();
+
+            for key in SETTING_KEYS {
+                if let Some(value) = settings.get(key)? {
+                    println!("{} = {}", key, value);
+                }
+            }
+
+            Ok(())
+        }
+        ManageConfigArgs::Convert { format, message } => {
+            if context.config.format() == format {
+                info!(
+                    "the configuration is already stored in {:?}",
+                    context.config.file_path()
+                );
+                return Ok(());
+            }
+
+            context.transaction(|| {
+                context
+                    .transaction
+                    .track(context.config.file_path_for(format))?;
+                context.config.convert(format)?;
+                context.commit(&message)
+            })
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::error;
+use thiserror::Error;
+
+use crate::config::merge_files;
+
+#[derive(Debug)]
+pub struct MergeConfigArgs {
+    pub base: PathBuf,
+    pub ours: PathBuf,
+    pub theirs: PathBuf,
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Error, Debug)]
+enum MergeConfigError {
+    #[error("{0} entries could not be merged, resolve them by hand")]
+    Conflicts(usize),
+}
+
+// runs as a git merge driver, so it works on the files git passes in and
+// does not need the twist repository
+pub fn merge_config(args: MergeConfigArgs) -> Result<()> {
+    let conflicts = merge_files(&args.base, &args.ours, &args.theirs, args.path.as_deref())?;
+
+    for conflict in &conflicts {
+        error!("{}", conflict);
+    }
+
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(MergeConfigError::Conflicts(conflicts.len()).into())
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    fs::{canonicalize, metadata, read_dir},
+    path::{absolute, Path, PathBuf},
+};
+
+use anyhow::Result;
+use log::info;
+use thiserror::Error;
+
+use super::Context;
+use crate::{
+    cli::ROOT_DIR_ENV,
+    files::{
+        git::GitRepository,
+        path::{xdg_root_dir, Paths},
+    },
+};
+
+#[derive(Debug)]
+pub struct MigrateRootArgs {
+    pub to: Option<PathBuf>,
+}
+
+#[derive(Error, Debug)]
+enum MigrateRootError {
+    #[error("could not find home directory to migrate to")]
+    HomeDirNotFound,
+    #[error("the destination {0} already exists and is not empty")]
+    DestinationNotEmpty(PathBuf),
+    #[error("the destination {0} is inside of the root directory {1}")]
+    DestinationInsideRoot(PathBuf, PathBuf),
+}
+
+pub fn migrate_root(args: MigrateRootArgs, context: Context) -> Result<()> {
+    let from = canonicalize(context.paths.root_dir())?;
+    let to = match args.to.or_else(xdg_root_dir) {
+        Some(to) => resolve_destination(&to)?,
+        None => return Err(MigrateRootError::HomeDirNotFound.into()),
+    };
+
+    if from == to {
+        info!("the root directory is already at {:?}", to);
+        return Ok(());
+    }
+
+    // moving the root into itself would copy it into its own subtree and then remove both
+    if to.starts_with(&from) {
+        return Err(MigrateRootError::DestinationInsideRoot(to, from).into());
+    }
+
+    if metadata(&to).is_ok() && read_dir(&to)?.next().is_some() {
+        return Err(MigrateRootError::DestinationNotEmpty(to).into());
+    }
+
+    drop(context);
+
+    Paths::ensure_parent_dir(&to)?;
+    GitRepository::relocate(&from, &to)?;
+
+    info!("moved the root directory from {:?} to {:?}", from, to);
+    info!("set the following environment variable to use it:");
+    println!("export {}={}", ROOT_DIR_ENV, to.display());
+
+    Ok(())
+}
+
+// the destination is stored in the git config and printed for the environment, so it
+// has to be absolute, and it is canonicalized up to its first missing component
+fn resolve_destination(to: &Path) -> Result<PathBuf> {
+    let to = absolute(to)?;
+    let mut existing = to.as_path();
+    let mut missing = vec![];
+
+    while metadata(existing).is_err() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return Ok(to),
+        }
+    }
+
+    let mut resolved = canonicalize(existing)?;
+    resolved.extend(missing.iter().rev());
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::current_dir;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::test_context;
+
+    #[test]
+    fn test_resolve_relative_destination() {
+        let cwd = canonicalize(current_dir().unwrap()).unwrap();
+
+        assert_eq!(
+            resolve_destination(Path::new("twist-root")).unwrap(),
+            cwd.join("twist-root")
+        );
+        assert_eq!(
+            resolve_destination(Path::new("../twist/root")).unwrap(),
+            cwd.parent().unwrap().join("twist/root")
+        );
+    }
+
+    #[test]
+    fn test_migrate_into_root_fails() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let root_dir = context.paths.root_dir.clone();
+        let to = root_dir.join("nested/root");
+
+        let err = migrate_root(
+            MigrateRootArgs {
+                to: Some(to.clone()),
+            },
+            context,
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(MigrateRootError::DestinationInsideRoot(_, _))
+        ));
+        assert!(root_dir.join(".git").exists());
+        assert!(!to.exists());
+    }
+
+    #[test]
+    fn test_migrate_root() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let root_dir = context.paths.root_dir.clone();
+        let to = dir.path().join("data/twist");
+
+        migrate_root(MigrateRootArgs { to: Some(to) }, context).unwrap();
+
+        assert!(!root_dir.exists());
+        assert!(dir.path().join("data/twist/.git").exists());
+    }
+}
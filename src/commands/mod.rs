@@ -1,44 +1,133 @@
 mod add_files;
+mod adopt_repo;
 mod apply_files;
+mod discover_files;
+mod doctor;
+mod entry_options;
 mod exec_git;
+mod export_profile;
+mod gc;
+mod import_files;
 mod init_repo;
+mod list_entries;
+mod manage_config;
+mod merge_config;
+mod migrate_root;
+mod move_entry;
+mod prompt;
 mod pull_from_remote;
 mod push_to_remote;
+mod relayout;
 mod remove_files;
 mod update_repository;
+mod which;
 
 use std::{path::Path, rc::Rc};
 
 use anyhow::Result;
+use log::{error, warn};
 
 use crate::{
     cli::*,
     config::*,
-    files::{git::GitRepository, path::*, FileManager},
+    files::{
+        git::GitRepository, layout::RepoLayout, path::*, transaction::Transaction, FileManager,
+    },
+    import::ImportSource,
 };
 
 use add_files::*;
+use adopt_repo::*;
 use apply_files::*;
+use discover_files::*;
+use doctor::*;
+use entry_options::*;
 use exec_git::*;
+use export_profile::*;
+use gc::*;
+use import_files::*;
 use init_repo::*;
+use list_entries::*;
+use manage_config::*;
+use merge_config::*;
+use migrate_root::*;
+use move_entry::*;
 use pull_from_remote::*;
 use push_to_remote::*;
+use relayout::*;
 use remove_files::*;
 use update_repository::*;
+use which::*;
 
 pub fn exec_command(cli: Cli) -> Result<()> {
+    if let CliCommand::MergeConfig(args) = cli.command {
+        return merge_config(MergeConfigArgs {
+            base: args.base,
+            ours: args.ours,
+            theirs: args.theirs,
+            path: args.path,
+        });
+    }
+
     let root_dir = root_dir(&cli.root_dir_override)?;
-    let context = Context::new(&root_dir, &cli.profile)?;
+
+    // init has to run before the context opens, and so creates, the repository
+    if let CliCommand::Init(args) = cli.command {
+        return init_repo(
+            InitRepoArgs {
+                message: args.message,
+                profile: args.profile.or(cli.profile),
+                remote: args.remote,
+                from: args.from,
+            },
+            &root_dir,
+        );
+    }
+
+    if let CliCommand::Adopt(args) = cli.command {
+        return adopt_repo(
+            AdoptRepoArgs {
+                message: args.message,
+                repo: args.repo,
+                layout: args.layout.into(),
+                profile: cli.profile,
+                yes: args.yes,
+                verify: !cli.no_verify,
+            },
+            Paths::new(&root_dir),
+        );
+    }
+
+    let mut context = Context::new(&root_dir, cli.profile.as_deref())?;
+    context.verify = !cli.no_verify;
+
+    for (nested_path, dir_path) in context.config.overlaps() {
+        warn!(
+            "{:?} is tracked both on its own and as part of {:?}, re-add it with --on-overlap to resolve this",
+            nested_path, dir_path
+        );
+    }
 
     match Command::new(cli, context) {
-        Command::InitRepo(args, context) => init_repo(args, context),
         Command::ExecGit(args, context) => exec_git(args, context),
         Command::AddFiles(args, context) => add_files(args, context),
         Command::RemoveFiles(args, context) => remove_files(args, context),
+        Command::MoveEntry(args, context) => move_entry(args, context),
+        Command::EntryOptions(args, context) => entry_options(args, context),
+        Command::ManageConfig(args, context) => manage_config(args, context),
+        Command::DiscoverFiles(args, context) => discover_files(args, context),
+        Command::ListEntries(args, context) => list_entries(args, context),
         Command::ApplyFiles(args, context) => apply_files(args, context),
         Command::UpdateRepository(args, context) => update_repository(args, context),
         Command::PushToRemote(args, context) => push_to_remote(args, context),
         Command::PullFromRemote(args, context) => pull_from_remote(args, context),
+        Command::MigrateRoot(args, context) => migrate_root(args, context),
+        Command::Relayout(args, context) => relayout(args, context),
+        Command::Which(args, context) => which(args, context),
+        Command::Doctor(args, context) => doctor(args, context),
+        Command::Gc(args, context) => gc(args, context),
+        Command::ImportFiles(args, context) => import_files(args, context),
+        Command::ExportProfile(args, context) => export_profile(args, context),
     }
 }
 
@@ -46,11 +135,22 @@ pub enum Command {
     ExecGit(ExecGitArgs, Context),
     AddFiles(AddFilesArgs, Context),
     RemoveFiles(RemoveFilesArgs, Context),
+    MoveEntry(MoveEntryArgs, Context),
+    EntryOptions(EntryOptionsArgs, Context),
+    ManageConfig(ManageConfigArgs, Context),
+    DiscoverFiles(DiscoverFilesArgs, Context),
+    ListEntries(ListEntriesArgs, Context),
     ApplyFiles(ApplyFilesArgs, Context),
     UpdateRepository(UpdateRepositoryArgs, Context),
-    InitRepo(InitRepoArgs, Context),
     PullFromRemote(PullFromRemoteArgs, Context),
     PushToRemote(PushToRemoteArgs, Context),
+    MigrateRoot(MigrateRootArgs, Context),
+    Relayout(RelayoutArgs, Context),
+    Which(WhichArgs, Context),
+    Doctor(DoctorArgs, Context),
+    Gc(GcArgs, Context),
+    ImportFiles(ImportFilesArgs, Context),
+    ExportProfile(ExportProfileArgs, Context),
 }
 
 pub struct Context {
@@ -58,22 +158,77 @@ pub struct Context {
     pub paths: Rc<Paths>,
     pub repo: Rc<GitRepository>,
     pub file_manager: Rc<FileManager>,
+    pub transaction: Rc<Transaction>,
+    pub verify: bool,
 }
 
 impl Context {
-    pub fn new(root_dir: &Path, profile: &str) -> Result<Self> {
-        let paths = Rc::new(Paths::new(root_dir));
+    pub fn new(root_dir: &Path, profile: Option<&str>) -> Result<Self> {
+        Self::with_paths(Paths::new(root_dir), profile)
+    }
+
+    pub fn with_paths(paths: Paths, profile: Option<&str>) -> Result<Self> {
+        let paths = Rc::new(paths);
         let config = Rc::new(ConfigManager::open(&paths));
-        let repo = Rc::new(GitRepository::open(&paths, profile)?);
-        let file_manager = Rc::new(FileManager::new(&config, &paths));
+        paths.set_layout(config.layout());
+
+        let settings = config.settings();
+        let profile = profile
+            .or(settings.default_profile.as_deref())
+            .unwrap_or(DEFAULT_PROFILE);
+        let repo = Rc::new(GitRepository::open(&paths, profile, &settings)?);
+
+        // clones made with plain git or by older versions get the driver on first use
+        let merge_patterns = config.merge_patterns();
+        if !repo.has_merge_driver(&merge_patterns) {
+            if let Err(err) = repo.register_merge_driver(&merge_patterns) {
+                warn!("failed to register the config merge driver: {}", err);
+            }
+        }
+        let transaction = Rc::new(Transaction::new(&paths));
+        let file_manager = Rc::new(FileManager::new(&config, &paths, &transaction));
 
         Ok(Self {
             config,
             paths,
             repo,
             file_manager,
+            transaction,
+            verify: true,
         })
     }
+
+    // validates the changed files before they are committed
+    pub fn commit(&self, message: &str) -> Result<()> {
+        if self.verify {
+            self.file_manager.validate(&self.repo.changed_files()?)?;
+        }
+
+        self.repo.commit(message)
+    }
+
+    pub fn transaction<F: FnOnce() -> Result<()>>(&self, f: F) -> Result<()> {
+        self.config.ensure_loaded()?;
+        self.transaction.begin()?;
+        for config_file_path in self.config.file_paths() {
+            self.transaction.track(config_file_path)?;
+        }
+
+        match f() {
+            Ok(()) => self.transaction.commit(),
+            Err(err) => {
+                if let Err(rollback_err) = self
+                    .transaction
+                    .rollback()
+                    .and_then(|_| self.repo.reset_index())
+                {
+                    error!("failed to roll back the changes: {}", rollback_err);
+                }
+
+                Err(err)
+            }
+        }
+    }
 }
 
 impl Command {
@@ -84,6 +239,9 @@ impl Command {
                 AddFilesArgs {
                     message: args.message,
                     paths: args.paths,
+                    apps: args.apps,
+                    overlap_policy: args.on_overlap.into(),
+                    config_file: args.config_file,
                 },
                 context,
             ),
@@ -94,6 +252,29 @@ impl Command {
                 },
                 context,
             ),
+            CliCommand::MoveEntry(args) => Command::MoveEntry(
+                MoveEntryArgs {
+                    message: args.message,
+                    old_path: args.old_path,
+                    new_path: args.new_path,
+                    repo_only: args.repo_only,
+                },
+                context,
+            ),
+            CliCommand::Entry(args) => Command::EntryOptions(args.command.into(), context),
+            CliCommand::Config(args) => Command::ManageConfig(args.command.into(), context),
+            CliCommand::Discover(args) => Command::DiscoverFiles(
+                DiscoverFilesArgs {
+                    message: args.message,
+                    list: args.list,
+                    yes: args.yes,
+                },
+                context,
+            ),
+            CliCommand::ListEntries(_args) => Command::ListEntries(ListEntriesArgs {}, context),
+            CliCommand::MergeConfig(_args) => {
+                unreachable!("merge-config runs without a context")
+            }
             CliCommand::ApplyFiles(_args) => Command::ApplyFiles(ApplyFilesArgs {}, context),
             CliCommand::UpdateRepository(args) => Command::UpdateRepository(
                 UpdateRepositoryArgs {
@@ -101,11 +282,304 @@ impl Command {
                 },
                 context,
             ),
-            CliCommand::Init(_args) => Command::InitRepo(InitRepoArgs {}, context),
+            CliCommand::Init(_args) => unreachable!("init runs without a context"),
+            CliCommand::Adopt(_args) => unreachable!("adopt runs without a context"),
             CliCommand::PullFromRemote(_args) => {
                 Command::PullFromRemote(PullFromRemoteArgs {}, context)
             }
             CliCommand::PushToRemote(_args) => Command::PushToRemote(PushToRemoteArgs {}, context),
+            CliCommand::MigrateRoot(args) => {
+                Command::MigrateRoot(MigrateRootArgs { to: args.to }, context)
+            }
+            CliCommand::Relayout(args) => Command::Relayout(
+                RelayoutArgs {
+                    layout: args.layout.into(),
+                    message: args.message,
+                },
+                context,
+            ),
+            CliCommand::Which(args) => Command::Which(WhichArgs { path: args.path }, context),
+            CliCommand::Doctor(args) => Command::Doctor(
+                DoctorArgs {
+                    message: args.message,
+                    fix: args.fix,
+                    fix_config: args.fix_config,
+                },
+                context,
+            ),
+            CliCommand::Import(args) => Command::ImportFiles(
+                ImportFilesArgs {
+                    message: args.message,
+                    source: args.from.into(),
+                    path: args.path,
+                },
+                context,
+            ),
+            CliCommand::Export(args) => Command::ExportProfile(
+                ExportProfileArgs {
+                    profile: args.profile,
+                    format: args.format.into(),
+                    output: args.output,
+                },
+                context,
+            ),
+            CliCommand::Gc(args) => Command::Gc(
+                GcArgs {
+                    message: args.message,
+                    yes: args.yes,
+                },
+                context,
+            ),
         }
     }
 }
+
+impl From<CliRepoLayout> for RepoLayout {
+    fn from(layout: CliRepoLayout) -> Self {
+        match layout {
+            CliRepoLayout::Default => RepoLayout::Default,
+            CliRepoLayout::KeepDots => RepoLayout::KeepDots,
+            CliRepoLayout::Chezmoi => RepoLayout::Chezmoi,
+            CliRepoLayout::ByApp => RepoLayout::ByApp,
+        }
+    }
+}
+
+impl From<EntryCliCommand> for EntryOptionsArgs {
+    fn from(command: EntryCliCommand) -> Self {
+        match command {
+            EntryCliCommand::Show { path } => EntryOptionsArgs::Show { path },
+            EntryCliCommand::Set {
+                message,
+                path,
+                options,
+            } => EntryOptionsArgs::Set {
+                message,
+                path,
+                options,
+            },
+            EntryCliCommand::Unset {
+                message,
+                path,
+                keys,
+            } => EntryOptionsArgs::Unset {
+                message,
+                path,
+                keys,
+            },
+        }
+    }
+}
+
+impl From<ConfigCliCommand> for ManageConfigArgs {
+    fn from(command: ConfigCliCommand) -> Self {
+        match command {
+            ConfigCliCommand::Get { key } => ManageConfigArgs::Get { key },
+            ConfigCliCommand::Set {
+                message,
+                key,
+                value,
+            } => ManageConfigArgs::Set {
+                message,
+                key,
+                value,
+            },
+            ConfigCliCommand::Unset { message, key } => ManageConfigArgs::Unset { message, key },
+            ConfigCliCommand::List => ManageConfigArgs::List,
+            ConfigCliCommand::Convert { message, to } => ManageConfigArgs::Convert {
+                format: to.into(),
+                message,
+            },
+        }
+    }
+}
+
+impl From<CliConfigFormat> for ConfigFormat {
+    fn from(format: CliConfigFormat) -> Self {
+        match format {
+            CliConfigFormat::Toml => ConfigFormat::Toml,
+            CliConfigFormat::Yaml => ConfigFormat::Yaml,
+            CliConfigFormat::Json => ConfigFormat::Json,
+        }
+    }
+}
+
+impl From<CliImportSource> for ImportSource {
+    fn from(source: CliImportSource) -> Self {
+        match source {
+            CliImportSource::Dotdrop => ImportSource::Dotdrop,
+            CliImportSource::Chezmoi => ImportSource::Chezmoi,
+            CliImportSource::Stow => ImportSource::Stow,
+            CliImportSource::Yadm => ImportSource::Yadm,
+        }
+    }
+}
+
+impl From<CliExportFormat> for ExportFormat {
+    fn from(format: CliExportFormat) -> Self {
+        match format {
+            CliExportFormat::Stow => ExportFormat::Stow,
+            CliExportFormat::Tar => ExportFormat::Tar,
+        }
+    }
+}
+
+impl From<CliOverlapPolicy> for OverlapPolicy {
+    fn from(policy: CliOverlapPolicy) -> Self {
+        match policy {
+            CliOverlapPolicy::Refuse => OverlapPolicy::Refuse,
+            CliOverlapPolicy::Merge => OverlapPolicy::Merge,
+            CliOverlapPolicy::Override => OverlapPolicy::Override,
+        }
+    }
+}
+
+// helpers for the tests of the commands, which run against a temporary root
+// and home directory
+#[cfg(test)]
+mod test_support {
+    use std::fs::{create_dir_all, write};
+    use std::path::{Path, PathBuf};
+
+    use git2::{Delta, DiffFindOptions, Repository, Signature};
+
+    use super::Context;
+    use crate::files::path::Paths;
+
+    pub fn test_context(dir: &Path) -> Context {
+        let home_dir = dir.join("home");
+        create_dir_all(&home_dir).unwrap();
+        Context::with_paths(Paths::new_with_home_dir(dir.join("root"), home_dir), None).unwrap()
+    }
+
+    pub fn commit_count(root_dir: &Path) -> usize {
+        let repo = Repository::open(root_dir).unwrap();
+        let mut revwalk = repo.revwalk().unwrap();
+
+        match revwalk.push_head() {
+            Ok(()) => revwalk.count(),
+            Err(_) => 0,
+        }
+    }
+
+    // a repository with the given files in a single commit
+    pub fn commit_files(dir: &Path, files: &[(&str, &str)]) -> Repository {
+        let repo = Repository::init(dir).unwrap();
+        let mut index = repo.index().unwrap();
+
+        for (path, contents) in files {
+            let full_path = dir.join(path);
+            create_dir_all(full_path.parent().unwrap()).unwrap();
+            write(&full_path, contents).unwrap();
+            index.add_path(Path::new(path)).unwrap();
+        }
+
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "fixture", &tree, &[])
+            .unwrap();
+        drop(tree);
+
+        repo
+    }
+
+    // the renames recorded by the last commit, anything else but a modification fails
+    pub fn head_renames(root_dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+        let repo = Repository::open(root_dir).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let parent_tree = head.parent(0).unwrap().tree().unwrap();
+        let mut diff = repo
+            .diff_tree_to_tree(Some(&parent_tree), Some(&head.tree().unwrap()), None)
+            .unwrap();
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+            .unwrap();
+
+        diff.deltas()
+            .filter(|d| d.status() != Delta::Modified)
+            .map(|d| {
+                assert_eq!(d.status(), Delta::Renamed);
+                (
+                    d.old_file().path().unwrap().to_path_buf(),
+                    d.new_file().path().unwrap().to_path_buf(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read, read_to_string, write};
+
+    use anyhow::anyhow;
+    use git2::Repository;
+    use tempfile::tempdir;
+
+    use super::test_support::{commit_count, test_context};
+    use crate::config::OverlapPolicy;
+
+    #[test]
+    fn test_transaction_rolls_back() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let home_dir = context.paths.home_dir.clone();
+        write(home_dir.join(".zshrc"), "export EDITOR=vim\n").unwrap();
+        create_dir_all(home_dir.join(".config/zsh")).unwrap();
+        write(home_dir.join(".config/zsh/aliases"), "alias ll='ls -l'\n").unwrap();
+
+        context
+            .transaction(|| {
+                context
+                    .file_manager
+                    .add(&[home_dir.join(".zshrc")], OverlapPolicy::Refuse)?;
+                context.config.save()?;
+                context.commit("add")
+            })
+            .unwrap();
+        let config_content = read(context.config.file_path()).unwrap();
+
+        let result = context.transaction(|| {
+            context.file_manager.add(
+                &[home_dir.join(".config/zsh/aliases")],
+                OverlapPolicy::Refuse,
+            )?;
+            context.config.save()?;
+            Err(anyhow!("failed after saving"))
+        });
+
+        assert!(result.is_err());
+        assert!(!context.paths.files_dir.join("home/config").exists());
+        assert_eq!(read(context.config.file_path()).unwrap(), config_content);
+        assert_eq!(commit_count(&context.paths.root_dir), 1);
+        assert!(!context
+            .paths
+            .root_dir
+            .join(".git/twist-transaction")
+            .exists());
+    }
+
+    #[test]
+    fn test_open_registers_merge_driver() {
+        let dir = tempdir().unwrap();
+        let root_dir = dir.path().join("root");
+        Repository::init(&root_dir).unwrap();
+        create_dir_all(root_dir.join("shared")).unwrap();
+        write(root_dir.join("shared/ssh.toml"), "").unwrap();
+        write(
+            root_dir.join("config.toml"),
+            "version = 1\ninclude = [\"shared/ssh.toml\"]\n",
+        )
+        .unwrap();
+
+        let context = test_context(dir.path());
+
+        let attributes = read_to_string(root_dir.join(".git/info/attributes")).unwrap();
+        assert!(attributes.lines().any(|l| l == "config.toml merge=twist"));
+        assert!(attributes
+            .lines()
+            .any(|l| l == "shared/ssh.toml merge=twist"));
+        assert!(context
+            .repo
+            .has_merge_driver(&context.config.merge_patterns()));
+    }
+}
@@ -0,0 +1,129 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use super::Context;
+
+#[derive(Debug)]
+pub struct MoveEntryArgs {
+    pub message: String,
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+    pub repo_only: bool,
+}
+
+pub fn move_entry(args: MoveEntryArgs, context: Context) -> Result<()> {
+    context.transaction(|| {
+        context
+            .file_manager
+            .move_entry(&args.old_path, &args.new_path, args.repo_only)?;
+        context.config.save()?;
+        context.commit(&args.message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, rename, write};
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::{head_renames, test_context};
+    use crate::config::{EntryOptions, OverlapPolicy};
+
+    fn tracked_zshrc(dir: &Path) -> Context {
+        let context = test_context(dir);
+        let zshrc_path = context.paths.home_dir.join(".zshrc");
+        write(&zshrc_path, "export EDITOR=vim\n").unwrap();
+
+        context
+            .transaction(|| {
+                context
+                    .file_manager
+                    .add(&[zshrc_path], OverlapPolicy::Refuse)?;
+                context.config.set_entry_options(
+                    Path::new("~/.zshrc"),
+                    EntryOptions {
+                        tags: vec!["zsh".to_string()],
+                        ..EntryOptions::default()
+                    },
+                )?;
+                context.config.save()?;
+                context.commit("add")
+            })
+            .unwrap();
+
+        context
+    }
+
+    fn args(old_path: PathBuf, new_path: PathBuf, repo_only: bool) -> MoveEntryArgs {
+        MoveEntryArgs {
+            message: "move".to_string(),
+            old_path,
+            new_path,
+            repo_only,
+        }
+    }
+
+    fn assert_moved(dir: &Path) {
+        let context = test_context(dir);
+        let new_src_path = Path::new("~/.config/zsh/zshrc");
+
+        assert!(context
+            .config
+            .entry_options(Path::new("~/.zshrc"))
+            .is_none());
+        assert_eq!(
+            context.config.entry_options(new_src_path).unwrap().tags,
+            vec!["zsh"]
+        );
+        assert_eq!(
+            head_renames(&context.paths.root_dir),
+            vec![(
+                PathBuf::from("dotfiles/home/zshrc"),
+                PathBuf::from("dotfiles/home/config/zsh/zshrc")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_move_entry() {
+        let dir = tempdir().unwrap();
+        let context = tracked_zshrc(dir.path());
+        let home_dir = context.paths.home_dir.clone();
+
+        let new_path = home_dir.join(".config/zsh/zshrc");
+        move_entry(
+            args(home_dir.join(".zshrc"), new_path.clone(), false),
+            context,
+        )
+        .unwrap();
+
+        assert!(!home_dir.join(".zshrc").exists());
+        assert!(new_path.exists());
+        assert_moved(dir.path());
+    }
+
+    #[test]
+    fn test_move_entry_repo_only() {
+        let dir = tempdir().unwrap();
+        let context = tracked_zshrc(dir.path());
+        let home_dir = context.paths.home_dir.clone();
+
+        // the file was already moved on the system
+        let new_path = home_dir.join(".config/zsh/zshrc");
+        create_dir_all(new_path.parent().unwrap()).unwrap();
+        rename(home_dir.join(".zshrc"), &new_path).unwrap();
+
+        move_entry(
+            args(home_dir.join(".zshrc"), new_path.clone(), true),
+            context,
+        )
+        .unwrap();
+
+        assert!(new_path.exists());
+        assert_moved(dir.path());
+    }
+}
@@ -0,0 +1,37 @@
+use std::io::{stdin, stdout, Write};
+
+use anyhow::Result;
+
+// anything but an explicit yes, including a closed stdin, declines
+pub fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    stdout().flush()?;
+
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// an empty answer or a closed stdin picks the first option
+pub fn choose(question: &str, options: &[String]) -> Result<usize> {
+    println!("{}", question);
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {}", i + 1, option);
+    }
+
+    loop {
+        print!("choose 1-{} [1] ", options.len());
+        stdout().flush()?;
+
+        let mut answer = String::new();
+        if stdin().read_line(&mut answer)? == 0 || answer.trim().is_empty() {
+            return Ok(0);
+        }
+
+        match answer.trim().parse::<usize>() {
+            Ok(n) if (1..=options.len()).contains(&n) => return Ok(n - 1),
+            _ => println!("{} is not one of the options", answer.trim()),
+        }
+    }
+}
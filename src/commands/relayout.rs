@@ -0,0 +1,94 @@
+use anyhow::Result;
+
+use super::Context;
+use crate::files::layout::RepoLayout;
+
+#[derive(Debug)]
+pub struct RelayoutArgs {
+    pub layout: RepoLayout,
+    pub message: String,
+}
+
+pub fn relayout(args: RelayoutArgs, context: Context) -> Result<()> {
+    context.transaction(|| {
+        context.file_manager.relayout(args.layout)?;
+        context.config.save()?;
+        context.commit(&args.message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+    use std::path::PathBuf;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::commands::test_support::{head_renames, test_context};
+    use crate::config::OverlapPolicy;
+
+    #[test]
+    fn test_relayout_renames_repo_paths() {
+        let dir = tempdir().unwrap();
+        let context = test_context(dir.path());
+        let home_dir = context.paths.home_dir.clone();
+        create_dir_all(home_dir.join(".config/nvim")).unwrap();
+        write(
+            home_dir.join(".config/nvim/init.lua"),
+            "vim.o.number = true\n",
+        )
+        .unwrap();
+        write(home_dir.join(".zshrc"), "export EDITOR=vim\n").unwrap();
+
+        context
+            .transaction(|| {
+                let paths = [home_dir.join(".zshrc"), home_dir.join(".config/nvim")];
+                context.file_manager.add(&paths, OverlapPolicy::Refuse)?;
+                context.config.save()?;
+                context.commit("add")
+            })
+            .unwrap();
+
+        let args = RelayoutArgs {
+            layout: RepoLayout::KeepDots,
+            message: "relayout".to_string(),
+        };
+        relayout(args, context).unwrap();
+
+        let context = test_context(dir.path());
+        let files_dir = context.paths.files_dir.clone();
+        assert_eq!(context.config.layout(), RepoLayout::KeepDots);
+        let files: Vec<_> = context
+            .config
+            .files()
+            .into_iter()
+            .map(|f| f.full_repo_path)
+            .collect();
+        let dirs: Vec<_> = context
+            .config
+            .dirs()
+            .into_iter()
+            .map(|d| d.full_repo_path)
+            .collect();
+        assert_eq!(files, vec![files_dir.join("home/.zshrc")]);
+        assert_eq!(dirs, vec![files_dir.join("home/.config/nvim")]);
+        assert!(files_dir.join("home/.config/nvim/init.lua").exists());
+        assert!(!files_dir.join("home/zshrc").exists());
+        assert!(!context.paths.root_dir.join(".relayout").exists());
+
+        assert_eq!(
+            head_renames(&context.paths.root_dir),
+            vec![
+                (
+                    PathBuf::from("dotfiles/home/config/nvim/init.lua"),
+                    PathBuf::from("dotfiles/home/.config/nvim/init.lua")
+                ),
+                (
+                    PathBuf::from("dotfiles/home/zshrc"),
+                    PathBuf::from("dotfiles/home/.zshrc")
+                ),
+            ]
+        );
+    }
+}
@@ -10,9 +10,9 @@ pub struct RemoveFilesArgs {
 }
 
 pub fn remove_files(args: RemoveFilesArgs, context: Context) -> Result<()> {
-    context.file_manager.remove(&args.paths)?;
-    context.config.save()?;
-    context.repo.commit(&args.message)?;
-
-    Ok(())
+    context.transaction(|| {
+        context.file_manager.remove(&args.paths)?;
+        context.config.save()?;
+        context.commit(&args.message)
+    })
 }
@@ -8,9 +8,9 @@ pub struct UpdateRepositoryArgs {
 }
 
 pub fn update_repository(args: UpdateRepositoryArgs, context: Context) -> Result<()> {
-    context.file_manager.update()?;
-    context.config.save()?;
-    context.repo.commit(&args.message)?;
-
-    Ok(())
+    context.transaction(|| {
+        context.file_manager.update()?;
+        context.config.save()?;
+        context.commit(&args.message)
+    })
 }
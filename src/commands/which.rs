@@ -0,0 +1,53 @@
+use std::path::{absolute, PathBuf};
+
+use anyhow::Result;
+use thiserror::Error;
+
+use super::Context;
+use crate::files::{FileManager, SyncStatus};
+
+#[derive(Debug)]
+pub struct WhichArgs {
+    pub path: PathBuf,
+}
+
+#[derive(Error, Debug)]
+enum WhichError {
+    #[error("{0} is not tracked")]
+    NotTracked(PathBuf),
+}
+
+pub fn which(args: WhichArgs, context: Context) -> Result<()> {
+    let path = absolute(&args.path)?;
+
+    let tracked_path = match context.file_manager.which(&path) {
+        Some(tracked_path) => tracked_path,
+        None => return Err(WhichError::NotTracked(path).into()),
+    };
+
+    let entry_kind = if tracked_path.entry_is_dir {
+        "directory"
+    } else {
+        "file"
+    };
+
+    let status =
+        match FileManager::compare(&tracked_path.full_src_path, &tracked_path.full_repo_path)? {
+            SyncStatus::Matches => "matches the repository copy",
+            SyncStatus::Differs => "differs from the repository copy",
+            SyncStatus::MissingFromRepo => "is missing from the repository",
+            SyncStatus::MissingFromSystem => "is missing from the system",
+        };
+
+    println!("{}", tracked_path.full_src_path.display());
+    println!(
+        "  entry:   {} ({})",
+        tracked_path.entry_src_path.display(),
+        entry_kind
+    );
+    println!("  repo:    {}", tracked_path.repo_path.display());
+    println!("  profile: {}", context.repo.profile().unwrap_or_default());
+    println!("  status:  {}", status);
+
+    Ok(())
+}
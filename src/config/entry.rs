@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use glob::Pattern;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+const MODE_KEY: &str = "mode";
+const EXCLUDES_KEY: &str = "excludes";
+const TAGS_KEY: &str = "tags";
+const CONDITIONS_KEY: &str = "conditions";
+const TEMPLATE_KEY: &str = "template";
+const ENCRYPTION_KEY: &str = "encryption";
+const VALIDATE_KEY: &str = "validate";
+const LIST_SEPARATOR: char = ',';
+
+#[derive(Error, Debug)]
+pub enum EntryOptionError {
+    #[error("unknown entry option: {0}")]
+    UnknownOption(String),
+    #[error("invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excludes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub template: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validate: Option<String>,
+}
+
+impl EntryOptions {
+    pub fn is_empty(&self) -> bool {
+        *self == EntryOptions::default()
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), EntryOptionError> {
+        let invalid = || EntryOptionError::InvalidValue(key.to_string(), value.to_string());
+
+        match key {
+            MODE_KEY => {
+                u32::from_str_radix(value, 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o7777)
+                    .ok_or_else(invalid)?;
+                self.mode = Some(value.to_string());
+            }
+            EXCLUDES_KEY => {
+                let excludes = Self::parse_list(value);
+                if excludes.iter().any(|e| Pattern::new(e).is_err()) {
+                    return Err(invalid());
+                }
+                self.excludes = excludes;
+            }
+            TAGS_KEY => self.tags = Self::parse_list(value),
+            CONDITIONS_KEY => {
+                let conditions = Self::parse_list(value);
+                if conditions.iter().any(|c| !c.contains('=')) {
+                    return Err(invalid());
+                }
+                self.conditions = conditions;
+            }
+            TEMPLATE_KEY => self.template = value.parse().map_err(|_| invalid())?,
+            ENCRYPTION_KEY => self.encryption = Some(value.to_string()),
+            VALIDATE_KEY => {
+                if value.trim().is_empty() {
+                    return Err(invalid());
+                }
+                self.validate = Some(value.to_string());
+            }
+            _ => return Err(EntryOptionError::UnknownOption(key.to_string())),
+        }
+
+        Ok(())
+    }
+
+    pub fn unset(&mut self, key: &str) -> Result<(), EntryOptionError> {
+        match key {
+            MODE_KEY => self.mode = None,
+            EXCLUDES_KEY => self.excludes.clear(),
+            TAGS_KEY => self.tags.clear(),
+            CONDITIONS_KEY => self.conditions.clear(),
+            TEMPLATE_KEY => self.template = false,
+            ENCRYPTION_KEY => self.encryption = None,
+            VALIDATE_KEY => self.validate = None,
+            _ => return Err(EntryOptionError::UnknownOption(key.to_string())),
+        }
+
+        Ok(())
+    }
+
+    // list options are merged, other options are only taken when set
+    pub fn extend(&mut self, other: &EntryOptions) {
+        for (list, other_list) in [
+            (&mut self.excludes, &other.excludes),
+            (&mut self.tags, &other.tags),
+            (&mut self.conditions, &other.conditions),
+        ] {
+            for value in other_list {
+                if !list.contains(value) {
+                    list.push(value.clone());
+                }
+            }
+        }
+
+        if other.mode.is_some() {
+            self.mode = other.mode.clone();
+        }
+        if other.encryption.is_some() {
+            self.encryption = other.encryption.clone();
+        }
+        if other.validate.is_some() {
+            self.validate = other.validate.clone();
+        }
+        self.template |= other.template;
+    }
+
+    fn parse_list(value: &str) -> Vec<String> {
+        value
+            .split(LIST_SEPARATOR)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ConfigEntryRepr {
+    Short(PathBuf),
+    Full {
+        path: PathBuf,
+        #[serde(flatten)]
+        options: EntryOptions,
+        // deny_unknown_fields does not work through flatten, so the keys that
+        // are left over are collected here and rejected
+        #[serde(flatten, skip_serializing)]
+        unknown: BTreeMap<String, IgnoredAny>,
+    },
+}
+
+// entries without options are written in the short `src = "repo path"` form
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "ConfigEntryRepr", into = "ConfigEntryRepr")]
+pub struct ConfigEntry {
+    pub path: PathBuf,
+    pub options: EntryOptions,
+}
+
+impl ConfigEntry {
+    pub fn new(path: PathBuf) -> Self {
+        ConfigEntry {
+            path,
+            options: EntryOptions::default(),
+        }
+    }
+}
+
+impl TryFrom<ConfigEntryRepr> for ConfigEntry {
+    type Error = EntryOptionError;
+
+    fn try_from(repr: ConfigEntryRepr) -> Result<Self, Self::Error> {
+        match repr {
+            ConfigEntryRepr::Short(path) => Ok(ConfigEntry::new(path)),
+            ConfigEntryRepr::Full {
+                path,
+                options,
+                unknown,
+            } => match unknown.into_keys().next() {
+                Some(key) => Err(EntryOptionError::UnknownOption(key)),
+                None => Ok(ConfigEntry { path, options }),
+            },
+        }
+    }
+}
+
+impl From<ConfigEntry> for ConfigEntryRepr {
+    fn from(entry: ConfigEntry) -> Self {
+        if entry.options.is_empty() {
+            ConfigEntryRepr::Short(entry.path)
+        } else {
+            ConfigEntryRepr::Full {
+                path: entry.path,
+                options: entry.options,
+                unknown: BTreeMap::new(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_short_and_full_forms() {
+        let entries: BTreeMap<PathBuf, ConfigEntry> = toml::from_str(
+            r#"
+            "~/.zshrc" = "home/zshrc"
+            "~/.ssh/config" = { path = "home/ssh/config", mode = "600", tags = ["ssh"] }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            entries[&PathBuf::from("~/.zshrc")],
+            ConfigEntry::new(PathBuf::from("home/zshrc"))
+        );
+
+        let ssh_config = &entries[&PathBuf::from("~/.ssh/config")];
+        assert_eq!(ssh_config.path, PathBuf::from("home/ssh/config"));
+        assert_eq!(ssh_config.options.mode, Some("600".to_string()));
+        assert_eq!(ssh_config.options.tags, vec!["ssh".to_string()]);
+    }
+
+    #[test]
+    fn test_serialize_short_form_without_options() {
+        let mut entries = BTreeMap::new();
+        entries.insert("a", ConfigEntry::new(PathBuf::from("home/a")));
+
+        assert_eq!(toml::to_string(&entries).unwrap(), "a = \"home/a\"\n");
+    }
+
+    #[test]
+    fn test_set_and_unset_options() {
+        let mut options = EntryOptions::default();
+
+        options.set("mode", "0600").unwrap();
+        options.set("tags", "shell, zsh").unwrap();
+        options.set("template", "true").unwrap();
+        assert_eq!(options.mode, Some("0600".to_string()));
+        assert_eq!(options.tags, vec!["shell".to_string(), "zsh".to_string()]);
+        assert!(options.template);
+
+        assert!(options.set("mode", "999").is_err());
+        assert!(options.set("excludes", "[a").is_err());
+        assert!(options.set("conditions", "linux").is_err());
+        assert!(options.set("validate", " ").is_err());
+        assert!(options.set("color", "blue").is_err());
+
+        options.unset("mode").unwrap();
+        options.unset("tags").unwrap();
+        options.unset("template").unwrap();
+        assert!(options.is_empty());
+    }
+
+    #[test]
+    fn test_extend_options() {
+        let mut options = EntryOptions {
+            mode: Some("0600".to_string()),
+            tags: vec!["shell".to_string()],
+            ..Default::default()
+        };
+
+        options.extend(&EntryOptions {
+            tags: vec!["shell".to_string(), "zsh".to_string()],
+            excludes: vec!["cache".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(options.mode, Some("0600".to_string()));
+        assert_eq!(options.tags, vec!["shell".to_string(), "zsh".to_string()]);
+        assert_eq!(options.excludes, vec!["cache".to_string()]);
+    }
+
+    #[test]
+    fn test_deserialize_unknown_option() {
+        let result: Result<BTreeMap<PathBuf, ConfigEntry>, _> =
+            toml::from_str(r#""~/.config" = { path = "home/config", excluds = ["cache"] }"#);
+
+        assert!(result.unwrap_err().to_string().contains("excluds"));
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use toml::Table;
+
+use super::{table_from_value, ConfigData, ConfigFilePersistence, SyntaxError};
+
+const JSON_CONFIG_FILE_NAME: &str = "config.json";
+
+pub(super) struct JsonConfigFilePersistence;
+
+impl ConfigFilePersistence for JsonConfigFilePersistence {
+    fn file_name() -> PathBuf {
+        PathBuf::from(JSON_CONFIG_FILE_NAME)
+    }
+
+    fn read(reader: &mut impl Read) -> Result<Table> {
+        let value: Result<serde_json::Value> = serde_json::from_reader(reader).map_err(|err| {
+            if err.is_io() {
+                return err.into();
+            }
+
+            let location = format!(" at line {} column {}", err.line(), err.column());
+            let message = err.to_string();
+
+            SyntaxError {
+                line: err.line(),
+                column: err.column(),
+                message: message.replace(&location, ""),
+            }
+            .into()
+        });
+
+        table_from_value(value?)
+    }
+
+    fn write(data: &ConfigData, _original: Option<&str>, writer: &mut impl Write) -> Result<()> {
+        serde_json::to_writer_pretty(&mut *writer, data)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_reports_location() {
+        let json = "{\n  \"files\": {\n    \"~/.zshrc\": home/zshrc\n  }\n}\n";
+
+        let err = JsonConfigFilePersistence::read(&mut json.as_bytes()).unwrap_err();
+        let err = err.downcast::<SyntaxError>().unwrap();
+
+        assert_eq!(err.line, 3);
+        assert_eq!(err.column, 17);
+    }
+}
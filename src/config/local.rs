@@ -0,0 +1,52 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufReader, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{toml::TomlConfigFilePersistence, ConfigError, ConfigFilePersistence, SyntaxError};
+
+const LOCAL_CONFIG_FILE_NAME: &str = "config.local.toml";
+
+// machine specific settings that are merged on top of the shared
+// configuration, the file is ignored by git
+#[derive(Deserialize, Debug, Default)]
+pub(super) struct LocalConfig {
+    #[serde(default)]
+    pub disabled: BTreeSet<PathBuf>,
+    #[serde(default)]
+    pub targets: BTreeMap<PathBuf, PathBuf>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+}
+
+impl LocalConfig {
+    pub fn load(root_dir: &Path) -> Result<Self, ConfigError> {
+        let path = root_dir.join(LOCAL_CONFIG_FILE_NAME);
+
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(ConfigError::Read(path, err.into())),
+        };
+
+        let config = TomlConfigFilePersistence::read(&mut BufReader::new(file)).map_err(|err| {
+            match err.downcast::<SyntaxError>() {
+                Ok(err) => ConfigError::Parse {
+                    path: path.clone(),
+                    line: err.line,
+                    column: err.column,
+                    message: err.message,
+                },
+                Err(err) => ConfigError::Read(path.clone(), err),
+            }
+        })?;
+
+        config
+            .try_into()
+            .map_err(|err| ConfigError::Invalid(path, err.into()))
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    fs::metadata,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use log::{debug, warn};
+
+use super::{source::ConfigSource, ConfigData, ConfigEntry, ConfigFormat, Settings, SETTING_KEYS};
+
+// merges the versions of a config file the way git passes them to a merge
+// driver, the result is written to the file with our version
+pub fn merge_files(
+    base_path: &Path,
+    ours_path: &Path,
+    theirs_path: &Path,
+    name: Option<&Path>,
+) -> Result<Vec<String>> {
+    let format = name
+        .and_then(ConfigFormat::from_path)
+        .unwrap_or(ConfigFormat::Toml);
+    debug!("merging {:?} as {:?}", name, format);
+
+    let load = |path: &Path| -> Result<ConfigData> {
+        // git passes an empty base when the branches share no ancestor
+        if metadata(path)?.len() == 0 {
+            return Ok(ConfigData::default());
+        }
+
+        Ok(ConfigSource::load(path.to_path_buf(), format)?.data)
+    };
+
+    let base = load(base_path)?;
+    let ours = load(ours_path)?;
+    let theirs = load(theirs_path)?;
+
+    let (merged, conflicts) = merge(&base, &ours, &theirs);
+
+    let mut source = ConfigSource::new(ours_path.to_path_buf(), format);
+    source.data = merged;
+    source.save()?;
+
+    Ok(conflicts)
+}
+
+pub fn merge(
+    base: &ConfigData,
+    ours: &ConfigData,
+    theirs: &ConfigData,
+) -> (ConfigData, Vec<String>) {
+    let mut conflicts = vec![];
+
+    let layout = merge_value(&base.layout, &ours.layout, &theirs.layout).unwrap_or_else(|| {
+        conflicts.push(format!(
+            "the layout was changed to {:?} in ours and {:?} in theirs",
+            ours.layout, theirs.layout
+        ));
+        ours.layout
+    });
+
+    let settings = merge_settings(&base.settings, &ours.settings, &theirs.settings);
+
+    let mut include = ours.include.clone();
+    include.extend(
+        theirs
+            .include
+            .iter()
+            .filter(|p| !ours.include.contains(p) && !base.include.contains(p))
+            .cloned(),
+    );
+
+    let mut merge_entries = |base, ours, theirs| {
+        merge_map(
+            base,
+            ours,
+            theirs,
+            |src_path: &PathBuf, base, ours, theirs| {
+                merge_entry(src_path, base, ours, theirs).map_err(|err| conflicts.push(err))
+            },
+        )
+    };
+
+    let files = merge_entries(&base.files.0, &ours.files.0, &theirs.files.0);
+    let dirs = merge_entries(&base.dirs.0, &ours.dirs.0, &theirs.dirs.0);
+
+    let overrides = merge_map(
+        &base.overrides,
+        &ours.overrides,
+        &theirs.overrides,
+        |nested_path: &PathBuf, _, ours, theirs| {
+            warn!(
+                "the override of {:?} was changed on both sides, keeping {:?} and dropping {:?}",
+                nested_path, ours, theirs
+            );
+            Ok(ours.clone())
+        },
+    );
+
+    let variables = merge_map(
+        &base.variables,
+        &ours.variables,
+        &theirs.variables,
+        |name: &String, _, ours, theirs| {
+            warn!(
+                "the variable {} was changed on both sides, keeping {:?} and dropping {:?}",
+                name, ours, theirs
+            );
+            Ok(ours.clone())
+        },
+    );
+
+    let mut merged = ConfigData {
+        version: ours.version.max(theirs.version),
+        layout,
+        include,
+        settings,
+        overrides,
+        variables,
+        ..ConfigData::default()
+    };
+    merged.files.0 = files;
+    merged.dirs.0 = dirs;
+
+    (merged, conflicts)
+}
+
+fn merge_entry(
+    src_path: &Path,
+    base: Option<&ConfigEntry>,
+    ours: &ConfigEntry,
+    theirs: &ConfigEntry,
+) -> Result<ConfigEntry, String> {
+    if ours.path != theirs.path {
+        return Err(format!(
+            "{:?} is stored at {:?} in ours and at {:?} in theirs",
+            src_path, ours.path, theirs.path
+        ));
+    }
+
+    // both sides agree on the repo path, so only the options differ
+    let options = match base {
+        Some(base) if base.options == ours.options => theirs.options.clone(),
+        _ => {
+            warn!(
+                "the options of {:?} were changed on both sides, keeping ours and dropping {:?}",
+                src_path, theirs.options
+            );
+            ours.options.clone()
+        }
+    };
+
+    Ok(ConfigEntry {
+        path: ours.path.clone(),
+        options,
+    })
+}
+
+// the settings are merged one key at a time, so that changes to different
+// keys do not conflict
+fn merge_settings(base: &Settings, ours: &Settings, theirs: &Settings) -> Settings {
+    let mut merged = ours.clone();
+
+    for key in SETTING_KEYS {
+        let value = |settings: &Settings| settings.get(key).ok().flatten();
+        let (ours_value, theirs_value) = (value(ours), value(theirs));
+
+        let result = match merge_value(&value(base), &ours_value, &theirs_value) {
+            Some(value) if value == ours_value => Ok(()),
+            Some(Some(value)) => merged.set(key, &value),
+            Some(None) => merged.unset(key),
+            None => {
+                warn!(
+                    "the setting {} was changed on both sides, keeping {:?} and dropping {:?}",
+                    key, ours_value, theirs_value
+                );
+                Ok(())
+            }
+        };
+
+        if let Err(err) = result {
+            warn!("failed to merge the setting {}: {}", key, err);
+        }
+    }
+
+    merged
+}
+
+fn merge_value<T: PartialEq + Clone>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || base == theirs {
+        Some(ours.clone())
+    } else if base == ours {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+fn merge_map<K, V>(
+    base: &BTreeMap<K, V>,
+    ours: &BTreeMap<K, V>,
+    theirs: &BTreeMap<K, V>,
+    mut resolve: impl FnMut(&K, Option<&V>, &V, &V) -> Result<V, ()>,
+) -> BTreeMap<K, V>
+where
+    K: Ord + Clone + Debug,
+    V: PartialEq + Clone,
+{
+    let mut merged = BTreeMap::new();
+
+    for key in ours.keys().chain(theirs.keys()) {
+        if merged.contains_key(key) {
+            continue;
+        }
+
+        let (base_value, ours_value, theirs_value) =
+            (base.get(key), ours.get(key), theirs.get(key));
+
+        let value = if ours_value == theirs_value || base_value == theirs_value {
+            ours_value.cloned()
+        } else if base_value == ours_value {
+            theirs_value.cloned()
+        } else {
+            match (ours_value, theirs_value) {
+                (Some(o), Some(t)) => Some(resolve(key, base_value, o, t).unwrap_or(o.clone())),
+                // an entry changed on one side and removed on the other is kept
+                (Some(v), None) | (None, Some(v)) => {
+                    debug!("keeping {:?}, it was changed on one side only", key);
+                    Some(v.clone())
+                }
+                (None, None) => None,
+            }
+        };
+
+        if let Some(value) = value {
+            merged.insert(key.clone(), value);
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> ConfigData {
+        ::toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_merge_takes_union_of_entries() {
+        let base =
+            config("[files]\n\"~/.zshrc\" = \"home/zshrc\"\n\"~/.vimrc\" = \"home/vimrc\"\n");
+        let ours = config("[files]\n\"~/.zshrc\" = \"home/zshrc\"\n\"~/.vimrc\" = \"home/vimrc\"\n\"~/.gitconfig\" = \"home/gitconfig\"\n");
+        let theirs = config(
+            "[files]\n\"~/.zshrc\" = \"home/zshrc\"\n\"~/.tmux.conf\" = \"home/tmux.conf\"\n",
+        );
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            merged.files.0.keys().collect::<Vec<_>>(),
+            vec![
+                Path::new("~/.gitconfig"),
+                Path::new("~/.tmux.conf"),
+                Path::new("~/.zshrc")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_reports_different_repo_paths() {
+        let base = config("");
+        let ours = config("[files]\n\"~/.zshrc\" = \"home/zshrc\"\n");
+        let theirs = config("[files]\n\"~/.zshrc\" = \"home/.zshrc\"\n");
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            merged.files.0[Path::new("~/.zshrc")].path,
+            PathBuf::from("home/zshrc")
+        );
+    }
+
+    #[test]
+    fn test_merge_settings_by_key() {
+        let base = Settings::default();
+        let mut ours = base.clone();
+        ours.set("committer-name", "Ours").unwrap();
+        ours.set("default-remote", "upstream").unwrap();
+        let mut theirs = base.clone();
+        theirs.set("committer-email", "theirs@example.com").unwrap();
+        theirs.set("default-remote", "origin").unwrap();
+
+        let merged = merge_settings(&base, &ours, &theirs);
+
+        assert_eq!(merged.committer_name.as_deref(), Some("Ours"));
+        assert_eq!(
+            merged.committer_email.as_deref(),
+            Some("theirs@example.com")
+        );
+        assert_eq!(merged.default_remote.as_deref(), Some("upstream"));
+    }
+}
@@ -0,0 +1,95 @@
+use log::{debug, info};
+use toml::{Table, Value};
+
+use super::ConfigError;
+
+pub const CURRENT_CONFIG_VERSION: u32 = 1;
+
+const VERSION_KEY: &str = "version";
+
+type Migration = fn(&mut Table);
+
+// the migration at index n upgrades a config from version n to version n + 1
+const MIGRATIONS: [Migration; CURRENT_CONFIG_VERSION as usize] = [migrate_unversioned];
+
+pub fn migrate(config: &mut Table) -> Result<(), ConfigError> {
+    let version = match config.get(VERSION_KEY) {
+        None => 0,
+        Some(value @ Value::Integer(version)) => {
+            u32::try_from(*version).map_err(|_| ConfigError::InvalidVersion(value.to_string()))?
+        }
+        Some(value) => return Err(ConfigError::InvalidVersion(value.to_string())),
+    };
+
+    if version > CURRENT_CONFIG_VERSION {
+        return Err(ConfigError::NewerVersion(version, CURRENT_CONFIG_VERSION));
+    }
+
+    for (from_version, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        debug!(
+            "migrating config from version {} to {}",
+            from_version,
+            from_version + 1
+        );
+        migration(config);
+    }
+
+    if version < CURRENT_CONFIG_VERSION {
+        info!(
+            "upgraded the config from version {} to {}, it will be saved in the new format on the next change",
+            version, CURRENT_CONFIG_VERSION
+        );
+    }
+
+    config.insert(
+        VERSION_KEY.to_string(),
+        Value::Integer(CURRENT_CONFIG_VERSION.into()),
+    );
+
+    Ok(())
+}
+
+fn migrate_unversioned(_config: &mut Table) {
+    // configs written before versioning only lack the version key, which is
+    // added once all migrations have run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_unversioned() {
+        let mut config: Table = toml::from_str("[files]\n\"~/.zshrc\" = \"home/zshrc\"\n").unwrap();
+
+        migrate(&mut config).unwrap();
+
+        assert_eq!(
+            config.get(VERSION_KEY),
+            Some(&Value::Integer(CURRENT_CONFIG_VERSION.into()))
+        );
+        assert!(config.contains_key("files"));
+    }
+
+    #[test]
+    fn test_migrate_newer_version() {
+        let mut config: Table = toml::from_str("version = 999\n").unwrap();
+
+        assert!(matches!(
+            migrate(&mut config),
+            Err(ConfigError::NewerVersion(999, CURRENT_CONFIG_VERSION))
+        ));
+    }
+
+    #[test]
+    fn test_migrate_invalid_version() {
+        for version in ["\"one\"", "-1", "4294967297"] {
+            let mut config: Table = toml::from_str(&format!("version = {version}\n")).unwrap();
+
+            assert!(matches!(
+                migrate(&mut config),
+                Err(ConfigError::InvalidVersion(_))
+            ));
+        }
+    }
+}
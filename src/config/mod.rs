@@ -1,102 +1,708 @@
+mod entry;
+mod json;
+mod local;
+mod merge;
+mod migrations;
+mod settings;
+mod source;
 mod toml;
+mod yaml;
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::BTreeMap,
     fmt::Debug,
-    fs::{File, OpenOptions},
-    io::{BufReader, BufWriter, Read, Write},
-    path::{Path, PathBuf},
+    fs::{read_dir, remove_file},
+    io::{ErrorKind, Read, Write},
+    path::{Component, Path, PathBuf},
     rc::Rc,
 };
 
 use anyhow::Result;
-use log::debug;
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
+use ::toml::Table;
+
+pub use self::entry::{ConfigEntry, EntryOptions};
+use self::json::JsonConfigFilePersistence;
+use self::local::LocalConfig;
+pub use self::merge::merge_files;
+use self::migrations::CURRENT_CONFIG_VERSION;
+pub use self::settings::{Settings, SETTING_KEYS};
+use self::source::ConfigSource;
 use self::toml::TomlConfigFilePersistence;
-use crate::files::path::{DirPathInfo, FilePathInfo, Paths};
+use self::yaml::YamlConfigFilePersistence;
+use crate::files::{
+    layout::RepoLayout,
+    path::{DirPathInfo, FilePathInfo, Paths},
+};
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-pub struct ConfigDirsData(BTreeMap<PathBuf, PathBuf>);
+const FRAGMENTS_DIR_NAME: &str = "config.d";
 
 #[derive(Serialize, Deserialize, Debug, Default)]
-pub struct ConfigFilesData(BTreeMap<PathBuf, PathBuf>);
+pub struct ConfigDirsData(BTreeMap<PathBuf, ConfigEntry>);
 
 #[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ConfigFilesData(BTreeMap<PathBuf, ConfigEntry>);
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ConfigData {
+    #[serde(default)]
+    version: u32,
+    #[serde(default, skip_serializing_if = "RepoLayout::is_default")]
+    layout: RepoLayout,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Settings::is_empty")]
+    settings: Settings,
     #[serde(default)]
     dirs: ConfigDirsData,
     #[serde(default)]
     files: ConfigFilesData,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    overrides: BTreeMap<PathBuf, PathBuf>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variables: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    Refuse,
+    Merge,
+    Override,
+}
+
+impl Default for ConfigData {
+    fn default() -> Self {
+        ConfigData {
+            version: CURRENT_CONFIG_VERSION,
+            layout: RepoLayout::default(),
+            include: vec![],
+            settings: Settings::default(),
+            dirs: ConfigDirsData::default(),
+            files: ConfigFilesData::default(),
+            overrides: BTreeMap::new(),
+            variables: BTreeMap::new(),
+        }
+    }
 }
 
 impl ConfigData {
     pub fn add_dir(&mut self, dir: &DirPathInfo) {
-        self.dirs
-            .0
-            .insert(dir.src_path.clone(), dir.config_repo_path.clone());
+        Self::add_entry(&mut self.dirs.0, &dir.src_path, &dir.config_repo_path);
     }
 
-    pub fn remove_dir(&mut self, path: &Path) {
-        self.dirs.0.remove(path);
+    pub fn remove_dir(&mut self, path: &Path) -> bool {
+        let removed = self.dirs.0.remove(path).is_some();
+        self.remove_overrides(path) || removed
     }
 
     pub fn add_file(&mut self, file: &FilePathInfo) {
-        self.files
-            .0
-            .insert(file.src_path.clone(), file.config_repo_path.clone());
+        Self::add_entry(&mut self.files.0, &file.src_path, &file.config_repo_path);
+    }
+
+    // re-adding an entry only updates its repo path and keeps its options
+    fn add_entry(entries: &mut BTreeMap<PathBuf, ConfigEntry>, src_path: &Path, repo_path: &Path) {
+        entries
+            .entry(src_path.to_path_buf())
+            .and_modify(|entry| entry.path = repo_path.to_path_buf())
+            .or_insert_with(|| ConfigEntry::new(repo_path.to_path_buf()));
+    }
+
+    fn entry_mut(&mut self, src_path: &Path) -> Option<&mut ConfigEntry> {
+        match self.files.0.get_mut(src_path) {
+            Some(entry) => Some(entry),
+            None => self.dirs.0.get_mut(src_path),
+        }
+    }
+
+    pub fn remove_file(&mut self, path: &Path) -> bool {
+        let removed = self.files.0.remove(path).is_some();
+        self.remove_overrides(path) || removed
+    }
+
+    pub fn add_override(&mut self, nested_path: &Path, dir_path: &Path) {
+        self.overrides
+            .insert(nested_path.to_path_buf(), dir_path.to_path_buf());
+    }
+
+    fn remove_overrides(&mut self, path: &Path) -> bool {
+        let count = self.overrides.len();
+        self.overrides
+            .retain(|nested_path, dir_path| nested_path != path && dir_path != path);
+        self.overrides.len() != count
     }
 
-    pub fn remove_file(&mut self, path: &Path) {
-        self.files.0.remove(path);
+    fn entry_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.0.keys().chain(self.dirs.0.keys())
     }
 }
 
+#[derive(Error, Debug)]
+#[error("{line}:{column}: {message}")]
+pub struct SyntaxError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl SyntaxError {
+    pub fn at_offset(content: &str, offset: usize, message: &str) -> Self {
+        let before = &content[..offset.min(content.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count()
+            + 1;
+
+        SyntaxError {
+            line,
+            column,
+            message: message.trim().replace('\n', ", "),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("failed to parse {path}:{line}:{column}: {message}")]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, anyhow::Error),
+    #[error("failed to load {0}: {1}")]
+    Invalid(PathBuf, anyhow::Error),
+    #[error("the config version {0} is not a valid version number")]
+    InvalidVersion(String),
+    #[error("the config was written by a newer version of twist (config version {0}, this version supports up to {1}), please upgrade twist")]
+    NewerVersion(u32, u32),
+    #[error("the format of {0} is not supported, use a .toml, .yaml or .json file")]
+    UnknownFormat(PathBuf),
+    #[error("{0} is not a tracked entry")]
+    UnknownEntry(PathBuf),
+    #[error("{0} is null, remove it or give it a value")]
+    NullValue(String),
+    #[error("the included configuration {0} is outside of the repository")]
+    IncludeOutsideRoot(PathBuf),
+    #[error("refusing to change the configuration because {0} could not be loaded, run `twist doctor --fix-config` to restore the last committed version")]
+    NotLoaded(PathBuf),
+}
+
+impl ConfigError {
+    // the file that failed to load, if the error is about a single file
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigError::Parse { path, .. } => Some(path),
+            ConfigError::Read(path, _)
+            | ConfigError::Invalid(path, _)
+            | ConfigError::UnknownFormat(path)
+            | ConfigError::IncludeOutsideRoot(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+// TOML has no null, so nulls in YAML and JSON configs are reported by their key
+// instead of failing the conversion with a type error, an empty file is empty
+fn table_from_value(value: serde_json::Value) -> Result<Table> {
+    fn find_null(value: &serde_json::Value, key: String) -> Option<String> {
+        match value {
+            serde_json::Value::Null => Some(key),
+            serde_json::Value::Array(values) => values
+                .iter()
+                .enumerate()
+                .find_map(|(index, value)| find_null(value, format!("{key}[{index}]"))),
+            serde_json::Value::Object(values) => values.iter().find_map(|(name, value)| {
+                let name = if key.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{key}.{name}")
+                };
+                find_null(value, name)
+            }),
+            _ => None,
+        }
+    }
+
+    if value.is_null() {
+        return Ok(Table::new());
+    }
+    if let Some(key) = find_null(&value, String::new()) {
+        return Err(ConfigError::NullValue(key).into());
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
 pub trait ConfigFilePersistence: Sized {
     fn file_name() -> PathBuf;
 
-    fn read(reader: &mut impl Read) -> Result<ConfigData>;
+    fn read(reader: &mut impl Read) -> Result<Table>;
+
+    fn write(config: &ConfigData, original: Option<&str>, writer: &mut impl Write) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    const ALL: [ConfigFormat; 3] = [ConfigFormat::Toml, ConfigFormat::Yaml, ConfigFormat::Json];
+
+    pub fn file_name(self) -> PathBuf {
+        match self {
+            ConfigFormat::Toml => TomlConfigFilePersistence::file_name(),
+            ConfigFormat::Yaml => YamlConfigFilePersistence::file_name(),
+            ConfigFormat::Json => JsonConfigFilePersistence::file_name(),
+        }
+    }
+
+    fn read(self, reader: &mut impl Read) -> Result<Table> {
+        match self {
+            ConfigFormat::Toml => TomlConfigFilePersistence::read(reader),
+            ConfigFormat::Yaml => YamlConfigFilePersistence::read(reader),
+            ConfigFormat::Json => JsonConfigFilePersistence::read(reader),
+        }
+    }
+
+    fn write(
+        self,
+        config: &ConfigData,
+        original: Option<&str>,
+        writer: &mut impl Write,
+    ) -> Result<()> {
+        match self {
+            ConfigFormat::Toml => TomlConfigFilePersistence::write(config, original, writer),
+            ConfigFormat::Yaml => YamlConfigFilePersistence::write(config, original, writer),
+            ConfigFormat::Json => JsonConfigFilePersistence::write(config, original, writer),
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(ConfigFormat::Toml),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+
+    // the files that are merged with the config merge driver
+    pub fn merge_patterns() -> Vec<String> {
+        Self::ALL
+            .into_iter()
+            .map(|format| format.file_name().display().to_string())
+            .chain([format!("{}/*.toml", FRAGMENTS_DIR_NAME)])
+            .collect()
+    }
+
+    // the first existing config file wins, a new repository starts with TOML
+    fn detect(root_dir: &Path) -> Self {
+        let mut existing = Self::ALL
+            .into_iter()
+            .filter(|format| root_dir.join(format.file_name()).exists());
+
+        let format = existing.next().unwrap_or(ConfigFormat::Toml);
 
-    fn write(config: &ConfigData, writer: &mut impl Write) -> Result<()>;
+        for ignored in existing {
+            warn!(
+                "ignoring {:?} because {:?} is used as the configuration",
+                ignored.file_name(),
+                format.file_name()
+            );
+        }
+
+        format
+    }
 }
 
 pub struct ConfigManager {
     paths: Rc<Paths>,
     config_data: RefCell<ConfigData>,
-    config_file_path: PathBuf,
+    sources: RefCell<Vec<ConfigSource>>,
+    target: Cell<usize>,
+    local: LocalConfig,
+    is_loaded: bool,
+    invalid_file: Option<PathBuf>,
 }
 
 impl ConfigManager {
     pub fn open(paths: &Rc<Paths>) -> Self {
-        let config_file_path = paths.root_dir.join(TomlConfigFilePersistence::file_name());
+        let format = ConfigFormat::detect(&paths.root_dir);
+        let config_file_path = paths.root_dir.join(format.file_name());
         debug!("reading configuration from {:?}", config_file_path);
 
-        let config_data = match File::open(&config_file_path) {
-            Ok(file) => {
-                let mut reader = BufReader::new(file);
-                TomlConfigFilePersistence::read(&mut reader).unwrap_or_default()
+        let loaded = Self::load(&paths.root_dir, config_file_path.clone(), format)
+            .and_then(|sources| Ok((sources, LocalConfig::load(&paths.root_dir)?)));
+
+        let (sources, local_config, invalid_file) = match loaded {
+            Ok((sources, local_config)) => (sources, local_config, None),
+            Err(err) => {
+                error!("{}", err);
+                let invalid_file = err.path().unwrap_or(&config_file_path).to_path_buf();
+                (
+                    vec![ConfigSource::new(config_file_path, format)],
+                    LocalConfig::default(),
+                    Some(invalid_file),
+                )
             }
-            _ => ConfigData::default(),
         };
 
+        let is_loaded = invalid_file.is_none();
+        Self {
+            invalid_file,
+            ..Self::with_sources(paths, sources, local_config, is_loaded)
+        }
+    }
+
+    fn with_sources(
+        paths: &Rc<Paths>,
+        sources: Vec<ConfigSource>,
+        local_config: LocalConfig,
+        is_loaded: bool,
+    ) -> Self {
+        let mut config_data = ConfigData {
+            layout: sources[0].data.layout,
+            include: sources[0].data.include.clone(),
+            settings: sources[0].data.settings.clone(),
+            ..ConfigData::default()
+        };
+
+        for source in &sources {
+            for (src_path, entry) in &source.data.files.0 {
+                if config_data
+                    .files
+                    .0
+                    .insert(src_path.clone(), entry.clone())
+                    .is_some()
+                {
+                    warn!(
+                        "{:?} is defined more than once, using the entry from {:?}",
+                        src_path, source.path
+                    );
+                }
+            }
+
+            for (src_path, entry) in &source.data.dirs.0 {
+                if config_data
+                    .dirs
+                    .0
+                    .insert(src_path.clone(), entry.clone())
+                    .is_some()
+                {
+                    warn!(
+                        "{:?} is defined more than once, using the entry from {:?}",
+                        src_path, source.path
+                    );
+                }
+            }
+
+            config_data.overrides.extend(source.data.overrides.clone());
+            config_data.variables.extend(source.data.variables.clone());
+        }
+
         Self {
             paths: paths.clone(),
             config_data: RefCell::new(config_data),
-            config_file_path,
+            sources: RefCell::new(sources),
+            target: Cell::new(0),
+            local: local_config,
+            is_loaded,
+            invalid_file: None,
+        }
+    }
+
+    // the main config is followed by its includes and then the fragments in
+    // config.d, in the order they are merged
+    fn load(
+        root_dir: &Path,
+        config_file_path: PathBuf,
+        format: ConfigFormat,
+    ) -> Result<Vec<ConfigSource>, ConfigError> {
+        let main = ConfigSource::load(config_file_path, format)?;
+
+        let mut fragment_paths = main
+            .data
+            .include
+            .iter()
+            .map(|p| Self::resolve_include(root_dir, p))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let fragments_dir = root_dir.join(FRAGMENTS_DIR_NAME);
+        let mut fragments = match read_dir(&fragments_dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .collect(),
+            Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+            Err(err) => return Err(ConfigError::Read(fragments_dir, err.into())),
+        };
+        fragments.sort();
+        fragment_paths.extend(fragments);
+
+        let mut sources = vec![main];
+
+        for path in fragment_paths {
+            if sources.iter().any(|s| s.path == path) {
+                continue;
+            }
+
+            if !path.exists() {
+                warn!("the included configuration {:?} does not exist", path);
+                continue;
+            }
+
+            let format = ConfigFormat::from_path(&path)
+                .ok_or_else(|| ConfigError::UnknownFormat(path.clone()))?;
+            debug!("reading configuration fragment from {:?}", path);
+            let fragment = ConfigSource::load(path, format)?;
+
+            if !fragment.data.layout.is_default()
+                || !fragment.data.include.is_empty()
+                || !fragment.data.settings.is_empty()
+            {
+                warn!(
+                    "{:?} sets the layout, includes or settings, which are only read from the main configuration",
+                    fragment.path
+                );
+            }
+
+            sources.push(fragment);
+        }
+
+        Ok(sources)
+    }
+
+    // includes are relative to the root directory and may not leave it, not
+    // even through a symlink
+    fn resolve_include(root_dir: &Path, include: &Path) -> Result<PathBuf, ConfigError> {
+        let include = include.strip_prefix(root_dir).unwrap_or(include);
+        let path = root_dir.join(include);
+        let is_relative = include
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        let is_inside = match (path.canonicalize(), root_dir.canonicalize()) {
+            (Ok(path), Ok(root_dir)) => path.starts_with(root_dir),
+            _ => true,
+        };
+
+        if is_relative && is_inside {
+            Ok(path)
+        } else {
+            Err(ConfigError::IncludeOutsideRoot(include.to_path_buf()))
+        }
+    }
+
+    pub fn ensure_loaded(&self) -> Result<()> {
+        if self.is_loaded {
+            Ok(())
+        } else {
+            Err(ConfigError::NotLoaded(self.file_path()).into())
+        }
+    }
+
+    // the file that kept the configuration from loading
+    pub fn invalid_file(&self) -> Option<&Path> {
+        self.invalid_file.as_deref()
+    }
+
+    pub fn file_path(&self) -> PathBuf {
+        self.sources.borrow()[0].path.clone()
+    }
+
+    pub fn file_paths(&self) -> Vec<PathBuf> {
+        self.sources
+            .borrow()
+            .iter()
+            .map(|s| s.path.clone())
+            .collect()
+    }
+
+    // the files git merges with the config merge driver, includes outside of
+    // the fragments directory are listed by their path
+    pub fn merge_patterns(&self) -> Vec<String> {
+        let mut patterns = ConfigFormat::merge_patterns();
+
+        for path in self.file_paths() {
+            let Ok(relative_path) = path.strip_prefix(&self.paths.root_dir) else {
+                continue;
+            };
+            let is_fragment = relative_path.parent() == Some(Path::new(FRAGMENTS_DIR_NAME))
+                && relative_path.extension().is_some_and(|ext| ext == "toml");
+            let pattern = relative_path.display().to_string();
+
+            if is_fragment || patterns.contains(&pattern) {
+                continue;
+            }
+
+            // attribute patterns end at the first space
+            if pattern.contains(char::is_whitespace) {
+                warn!(
+                    "{:?} contains a space, git merges it line by line",
+                    relative_path
+                );
+                continue;
+            }
+
+            patterns.push(pattern);
+        }
+
+        patterns
+    }
+
+    pub fn format(&self) -> ConfigFormat {
+        self.sources.borrow()[0].format
+    }
+
+    pub fn file_path_for(&self, format: ConfigFormat) -> PathBuf {
+        self.paths.root_dir.join(format.file_name())
+    }
+
+    pub fn set_target_file(&self, path: &Path) -> Result<()> {
+        let path = Self::resolve_include(&self.paths.root_dir, path)?;
+        let format = ConfigFormat::from_path(&path)
+            .ok_or_else(|| ConfigError::UnknownFormat(path.clone()))?;
+
+        let mut sources = self.sources.borrow_mut();
+
+        if let Some(index) = sources.iter().position(|s| s.path == path) {
+            self.target.set(index);
+            return Ok(());
+        }
+
+        // new fragments outside of config.d are only read when included
+        let fragments_dir = self.paths.root_dir.join(FRAGMENTS_DIR_NAME);
+        if path.parent() != Some(fragments_dir.as_path()) || format != ConfigFormat::Toml {
+            let include_path = path
+                .strip_prefix(&self.paths.root_dir)
+                .unwrap_or(&path)
+                .to_path_buf();
+            debug!("including {:?} in the configuration", include_path);
+            self.config_data
+                .borrow_mut()
+                .include
+                .push(include_path.clone());
+            sources[0].data.include.push(include_path);
+            sources[0].changed = true;
+        }
+
+        sources.push(ConfigSource::new(path, format));
+        self.target.set(sources.len() - 1);
+
+        Ok(())
+    }
+
+    pub fn set_target_entry(&self, src_path: &Path) {
+        if let Some(index) = self
+            .sources
+            .borrow()
+            .iter()
+            .position(|s| s.contains(src_path))
+        {
+            self.target.set(index);
+        }
+    }
+
+    pub fn convert(&self, format: ConfigFormat) -> Result<()> {
+        self.ensure_loaded()?;
+
+        let old_file_path = self.file_path();
+        let new_file_path = self.file_path_for(format);
+
+        info!("converting {:?} to {:?}", old_file_path, new_file_path);
+
+        {
+            let mut sources = self.sources.borrow_mut();
+            sources[0].format = format;
+            sources[0].path = new_file_path;
+            sources[0].changed = true;
+        }
+        self.save()?;
+
+        match remove_file(&old_file_path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
         }
     }
 
     pub fn save(&self) -> Result<()> {
-        let config_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.config_file_path)?;
+        self.ensure_loaded()?;
 
-        let mut writer = BufWriter::new(config_file);
+        for source in self.sources.borrow_mut().iter_mut().filter(|s| s.changed) {
+            source.save()?;
+            source.changed = false;
+        }
 
-        TomlConfigFilePersistence::write(&self.config_data.borrow(), &mut writer)
+        Ok(())
+    }
+
+    // changes to an entry go to the file that defines it, or to the target
+    // file when it is new
+    fn update_entry(&self, src_path: &Path, f: impl Fn(&mut ConfigData)) {
+        f(&mut self.config_data.borrow_mut());
+
+        let mut sources = self.sources.borrow_mut();
+        let mut owners: Vec<_> = sources
+            .iter_mut()
+            .filter(|s| s.contains(src_path))
+            .collect();
+
+        if owners.is_empty() {
+            let source = &mut sources[self.target.get()];
+            f(&mut source.data);
+            source.changed = true;
+        } else {
+            for source in owners.iter_mut() {
+                f(&mut source.data);
+                source.changed = true;
+            }
+        }
+    }
+
+    fn remove_entry(&self, f: impl Fn(&mut ConfigData) -> bool) {
+        f(&mut self.config_data.borrow_mut());
+
+        for source in self.sources.borrow_mut().iter_mut() {
+            if f(&mut source.data) {
+                source.changed = true;
+            }
+        }
+    }
+
+    pub fn layout(&self) -> RepoLayout {
+        self.config_data.borrow().layout
+    }
+
+    pub fn set_layout(&self, layout: RepoLayout) {
+        self.config_data.borrow_mut().layout = layout;
+
+        let main = &mut self.sources.borrow_mut()[0];
+        main.data.layout = layout;
+        main.changed = true;
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.config_data.borrow().settings.clone()
+    }
+
+    pub fn set_settings(&self, settings: Settings) {
+        self.config_data.borrow_mut().settings = settings.clone();
+
+        let main = &mut self.sources.borrow_mut()[0];
+        main.data.settings = settings;
+        main.changed = true;
     }
 
     pub fn files(&self) -> Vec<FilePathInfo> {
@@ -105,9 +711,14 @@ impl ConfigManager {
             .files
             .0
             .iter()
-            .map(|(src_path, config_repo_path)| {
-                self.paths
-                    .resolve_file_paths_from_config_paths(src_path, config_repo_path)
+            .map(|(src_path, entry)| {
+                let mut file = self
+                    .paths
+                    .resolve_file_paths_from_config_paths(src_path, &entry.path);
+                if let Some(target) = self.local.targets.get(src_path) {
+                    file.full_src_path = self.paths.full_src_path(target);
+                }
+                file
             })
             .collect()
     }
@@ -122,25 +733,134 @@ impl ConfigManager {
 
     pub fn add_files(&self, files: &[FilePathInfo]) {
         for file in files {
-            self.config_data.borrow_mut().add_file(file);
+            self.update_entry(&file.src_path, |data| data.add_file(file));
         }
     }
 
     pub fn remove_files(&self, paths: &[FilePathInfo]) {
         for path in paths {
-            self.config_data.borrow_mut().remove_file(&path.src_path);
+            self.remove_entry(|data| data.remove_file(&path.src_path));
         }
     }
 
+    pub fn containing_dirs(&self, src_path: &Path) -> Vec<DirPathInfo> {
+        self.dirs()
+            .into_iter()
+            .filter(|d| src_path != d.src_path && src_path.starts_with(&d.src_path))
+            .collect()
+    }
+
+    pub fn contained_entries(&self, src_path: &Path) -> (Vec<FilePathInfo>, Vec<DirPathInfo>) {
+        let is_contained = |p: &Path| p != src_path && p.starts_with(src_path);
+
+        let files = self
+            .files()
+            .into_iter()
+            .filter(|f| is_contained(&f.src_path))
+            .collect();
+        let dirs = self
+            .dirs()
+            .into_iter()
+            .filter(|d| is_contained(&d.src_path))
+            .collect();
+
+        (files, dirs)
+    }
+
+    pub fn is_disabled(&self, src_path: &Path) -> bool {
+        self.local.disabled.contains(src_path)
+    }
+
+    pub fn local_target(&self, src_path: &Path) -> Option<PathBuf> {
+        self.local.targets.get(src_path).cloned()
+    }
+
+    pub fn is_local_variable(&self, name: &str) -> bool {
+        self.local.variables.contains_key(name)
+    }
+
+    pub fn variables(&self) -> BTreeMap<String, String> {
+        let mut variables = self.config_data.borrow().variables.clone();
+        variables.extend(self.local.variables.clone());
+        variables
+    }
+
+    pub fn entry_options(&self, src_path: &Path) -> Option<EntryOptions> {
+        let config_data = self.config_data.borrow();
+
+        config_data
+            .files
+            .0
+            .get(src_path)
+            .or_else(|| config_data.dirs.0.get(src_path))
+            .map(|entry| entry.options.clone())
+    }
+
+    pub fn set_entry_options(&self, src_path: &Path, options: EntryOptions) -> Result<()> {
+        if self.entry_options(src_path).is_none() {
+            return Err(ConfigError::UnknownEntry(src_path.to_path_buf()).into());
+        }
+
+        self.update_entry(src_path, |data| {
+            if let Some(entry) = data.entry_mut(src_path) {
+                entry.options = options.clone();
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn add_override(&self, nested_path: &Path, dir_path: &Path) {
+        debug!("recording {:?} as overriding {:?}", nested_path, dir_path);
+        self.update_entry(nested_path, |data| data.add_override(nested_path, dir_path));
+    }
+
+    pub fn overrides_in(&self, dir_path: &Path) -> Vec<PathBuf> {
+        self.config_data
+            .borrow()
+            .overrides
+            .iter()
+            .filter(|(_, d)| *d == dir_path)
+            .map(|(nested_path, _)| nested_path.clone())
+            .collect()
+    }
+
+    pub fn overlaps(&self) -> Vec<(PathBuf, PathBuf)> {
+        let config_data = self.config_data.borrow();
+
+        config_data
+            .entry_paths()
+            .flat_map(|nested_path| {
+                config_data
+                    .dirs
+                    .0
+                    .keys()
+                    .filter(move |dir_path| {
+                        nested_path != *dir_path && nested_path.starts_with(dir_path)
+                    })
+                    .map(move |dir_path| (nested_path, dir_path))
+            })
+            .filter(|(nested_path, dir_path)| {
+                config_data.overrides.get(*nested_path) != Some(*dir_path)
+            })
+            .map(|(nested_path, dir_path)| (nested_path.clone(), dir_path.clone()))
+            .collect()
+    }
+
     pub fn dirs(&self) -> Vec<DirPathInfo> {
         self.config_data
             .borrow()
             .dirs
             .0
             .iter()
-            .map(|(src_path, config_repo_path)| {
-                self.paths
-                    .resolve_dir_paths_from_config_paths(src_path, config_repo_path)
+            .map(|(src_path, entry)| {
+                let mut dir = self
+                    .paths
+                    .resolve_dir_paths_from_config_paths(src_path, &entry.path);
+                if let Some(target) = self.local.targets.get(src_path) {
+                    dir.full_src_path = self.paths.full_src_path(target);
+                }
+                dir
             })
             .collect()
     }
@@ -151,13 +871,181 @@ impl ConfigManager {
 
     pub fn add_dirs(&self, paths: &[DirPathInfo]) {
         for path in paths {
-            self.config_data.borrow_mut().add_dir(path);
+            self.update_entry(&path.src_path, |data| data.add_dir(path));
         }
     }
 
     pub fn remove_dirs(&self, paths: &[DirPathInfo]) {
         for path in paths {
-            self.config_data.borrow_mut().remove_dir(&path.src_path);
+            self.remove_entry(|data| data.remove_dir(&path.src_path));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_manager() -> ConfigManager {
+        let paths = Rc::new(Paths::new("/home/user/.twist"));
+        let config = ConfigManager::with_sources(
+            &paths,
+            vec![ConfigSource::new(
+                PathBuf::from("/home/user/.twist/config.toml"),
+                ConfigFormat::Toml,
+            )],
+            LocalConfig::default(),
+            true,
+        );
+
+        config.add_dirs(&[paths.resolve_dir_paths_from_config_paths("~/.config", "home/config")]);
+        config.add_files(&[paths.resolve_file_paths_from_config_paths(
+            "~/.config/nvim/init.lua",
+            "home/config/nvim/init.lua",
+        )]);
+
+        config
+    }
+
+    #[test]
+    fn test_syntax_error_at_offset() {
+        let err = SyntaxError::at_offset("a = 1\nb = \n", 10, "invalid string\nexpected a value\n");
+
+        assert_eq!(err.line, 2);
+        assert_eq!(err.column, 5);
+        assert_eq!(err.message, "invalid string, expected a value");
+    }
+
+    #[test]
+    fn test_resolve_include() {
+        let root_dir = Path::new("/home/user/.twist");
+
+        assert_eq!(
+            ConfigManager::resolve_include(root_dir, Path::new("work.toml")).unwrap(),
+            root_dir.join("work.toml")
+        );
+        assert_eq!(
+            ConfigManager::resolve_include(root_dir, &root_dir.join("config.d/work.toml")).unwrap(),
+            root_dir.join("config.d/work.toml")
+        );
+        for include in ["../work.toml", "config.d/../../work.toml", "/etc/work.toml"] {
+            assert!(matches!(
+                ConfigManager::resolve_include(root_dir, Path::new(include)),
+                Err(ConfigError::IncludeOutsideRoot(_))
+            ));
         }
     }
+
+    #[test]
+    fn test_containing_dirs() {
+        let config = config_manager();
+
+        let dirs = config.containing_dirs(Path::new("~/.config/starship.toml"));
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(dirs[0].src_path, PathBuf::from("~/.config"));
+
+        assert!(config.containing_dirs(Path::new("~/.config")).is_empty());
+        assert!(config.containing_dirs(Path::new("~/.configs")).is_empty());
+    }
+
+    #[test]
+    fn test_contained_entries() {
+        let config = config_manager();
+
+        let (files, dirs) = config.contained_entries(Path::new("~"));
+        assert_eq!(files.len(), 1);
+        assert_eq!(dirs.len(), 1);
+
+        let (files, dirs) = config.contained_entries(Path::new("~/.config/nvim"));
+        assert_eq!(files[0].src_path, PathBuf::from("~/.config/nvim/init.lua"));
+        assert!(dirs.is_empty());
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let config = config_manager();
+
+        assert_eq!(
+            config.overlaps(),
+            vec![(
+                PathBuf::from("~/.config/nvim/init.lua"),
+                PathBuf::from("~/.config")
+            )]
+        );
+
+        config.add_override(Path::new("~/.config/nvim/init.lua"), Path::new("~/.config"));
+        assert!(config.overlaps().is_empty());
+
+        config.remove_dirs(&[config.dirs().remove(0)]);
+        assert!(config.overrides_in(Path::new("~/.config")).is_empty());
+    }
+
+    #[test]
+    fn test_entries_are_written_to_their_source() {
+        let paths = Rc::new(Paths::new("/home/user/.twist"));
+        let mut fragment = ConfigSource::new(
+            PathBuf::from("/home/user/.twist/config.d/work.toml"),
+            ConfigFormat::Toml,
+        );
+        fragment.data.add_file(
+            &paths.resolve_file_paths_from_config_paths("~/.gitconfig", "home/gitconfig"),
+        );
+
+        let config = ConfigManager::with_sources(
+            &paths,
+            vec![
+                ConfigSource::new(
+                    PathBuf::from("/home/user/.twist/config.toml"),
+                    ConfigFormat::Toml,
+                ),
+                fragment,
+            ],
+            LocalConfig::default(),
+            true,
+        );
+        assert_eq!(config.files().len(), 1);
+
+        config.add_files(&[
+            paths.resolve_file_paths_from_config_paths("~/.gitconfig", "home/git/config"),
+            paths.resolve_file_paths_from_config_paths("~/.zshrc", "home/zshrc"),
+        ]);
+
+        let sources = config.sources.borrow();
+        assert!(sources[0].contains(Path::new("~/.zshrc")));
+        assert!(!sources[0].contains(Path::new("~/.gitconfig")));
+        assert_eq!(
+            sources[1].data.files.0[Path::new("~/.gitconfig")].path,
+            PathBuf::from("home/git/config")
+        );
+    }
+
+    #[test]
+    fn test_local_overrides() {
+        let paths = Rc::new(Paths::new("/home/user/.twist"));
+        let mut main = ConfigSource::new(
+            PathBuf::from("/home/user/.twist/config.toml"),
+            ConfigFormat::Toml,
+        );
+        main.data
+            .add_file(&paths.resolve_file_paths_from_config_paths("~/.zshrc", "home/zshrc"));
+        main.data.variables.insert("email".into(), "me@home".into());
+
+        let mut local_config = LocalConfig::default();
+        local_config.disabled.insert(PathBuf::from("~/.zshrc"));
+        local_config
+            .targets
+            .insert(PathBuf::from("~/.zshrc"), PathBuf::from("/work/zshrc"));
+        local_config
+            .variables
+            .insert("email".into(), "me@work".into());
+
+        let config = ConfigManager::with_sources(&paths, vec![main], local_config, true);
+
+        assert!(config.is_disabled(Path::new("~/.zshrc")));
+        assert_eq!(
+            config.files()[0].full_src_path,
+            PathBuf::from("/work/zshrc")
+        );
+        assert_eq!(config.variables()["email"], "me@work");
+    }
 }
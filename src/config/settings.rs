@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+const COMMITTER_NAME_KEY: &str = "committer-name";
+const COMMITTER_EMAIL_KEY: &str = "committer-email";
+const DEFAULT_REMOTE_KEY: &str = "default-remote";
+const DEFAULT_PROFILE_KEY: &str = "default-profile";
+const LINK_MODE_KEY: &str = "link-mode";
+
+pub const SETTING_KEYS: [&str; 5] = [
+    COMMITTER_NAME_KEY,
+    COMMITTER_EMAIL_KEY,
+    DEFAULT_REMOTE_KEY,
+    DEFAULT_PROFILE_KEY,
+    LINK_MODE_KEY,
+];
+
+#[derive(Error, Debug)]
+pub enum SettingError {
+    #[error("unknown setting: {0}, expected one of {keys}", keys = SETTING_KEYS.join(", "))]
+    UnknownSetting(String),
+    #[error("invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkMode {
+    Copy,
+    Symlink,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Settings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub committer_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub committer_email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_remote: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_mode: Option<LinkMode>,
+}
+
+impl Settings {
+    pub fn is_empty(&self) -> bool {
+        *self == Settings::default()
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, SettingError> {
+        let value = match key {
+            COMMITTER_NAME_KEY => self.committer_name.clone(),
+            COMMITTER_EMAIL_KEY => self.committer_email.clone(),
+            DEFAULT_REMOTE_KEY => self.default_remote.clone(),
+            DEFAULT_PROFILE_KEY => self.default_profile.clone(),
+            LINK_MODE_KEY => self.link_mode.map(|mode| match mode {
+                LinkMode::Copy => "copy".to_string(),
+                LinkMode::Symlink => "symlink".to_string(),
+            }),
+            _ => return Err(SettingError::UnknownSetting(key.to_string())),
+        };
+
+        Ok(value)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), SettingError> {
+        let invalid = || SettingError::InvalidValue(key.to_string(), value.to_string());
+
+        if value.trim().is_empty() {
+            return Err(invalid());
+        }
+
+        match key {
+            COMMITTER_NAME_KEY => self.committer_name = Some(value.to_string()),
+            COMMITTER_EMAIL_KEY => {
+                if !value.contains('@') {
+                    return Err(invalid());
+                }
+                self.committer_email = Some(value.to_string());
+            }
+            DEFAULT_REMOTE_KEY => self.default_remote = Some(value.to_string()),
+            DEFAULT_PROFILE_KEY => self.default_profile = Some(value.to_string()),
+            LINK_MODE_KEY => {
+                self.link_mode = Some(match value {
+                    "copy" => LinkMode::Copy,
+                    "symlink" => LinkMode::Symlink,
+                    _ => return Err(invalid()),
+                })
+            }
+            _ => return Err(SettingError::UnknownSetting(key.to_string())),
+        }
+
+        Ok(())
+    }
+
+    pub fn unset(&mut self, key: &str) -> Result<(), SettingError> {
+        match key {
+            COMMITTER_NAME_KEY => self.committer_name = None,
+            COMMITTER_EMAIL_KEY => self.committer_email = None,
+            DEFAULT_REMOTE_KEY => self.default_remote = None,
+            DEFAULT_PROFILE_KEY => self.default_profile = None,
+            LINK_MODE_KEY => self.link_mode = None,
+            _ => return Err(SettingError::UnknownSetting(key.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_and_unset() {
+        let mut settings = Settings::default();
+
+        settings.set("committer-email", "me@example.org").unwrap();
+        settings.set("link-mode", "symlink").unwrap();
+        assert_eq!(
+            settings.get("committer-email").unwrap(),
+            Some("me@example.org".to_string())
+        );
+        assert_eq!(
+            settings.get("link-mode").unwrap(),
+            Some("symlink".to_string())
+        );
+
+        assert!(settings.set("committer-email", "nobody").is_err());
+        assert!(settings.set("link-mode", "hardlink").is_err());
+        assert!(settings.set("editor", "vim").is_err());
+
+        settings.unset("committer-email").unwrap();
+        settings.unset("link-mode").unwrap();
+        assert!(settings.is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    ffi::OsString,
+    fs::{create_dir_all, read_to_string, remove_file, rename, File, OpenOptions},
+    io::{BufReader, BufWriter, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use log::debug;
+
+use super::{migrations::migrate, ConfigData, ConfigError, ConfigFormat, SyntaxError};
+
+const TEMP_FILE_EXTENSION: &str = ".tmp";
+
+// a single configuration file, either the main config or one of its fragments
+pub(super) struct ConfigSource {
+    pub path: PathBuf,
+    pub format: ConfigFormat,
+    pub data: ConfigData,
+    pub changed: bool,
+}
+
+impl ConfigSource {
+    pub fn new(path: PathBuf, format: ConfigFormat) -> Self {
+        ConfigSource {
+            path,
+            format,
+            data: ConfigData::default(),
+            changed: false,
+        }
+    }
+
+    pub fn load(path: PathBuf, format: ConfigFormat) -> Result<Self, ConfigError> {
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::new(path, format)),
+            Err(err) => return Err(ConfigError::Read(path, err.into())),
+        };
+
+        let mut reader = BufReader::new(file);
+
+        let mut config =
+            format
+                .read(&mut reader)
+                .map_err(|err| match err.downcast::<SyntaxError>() {
+                    Ok(err) => ConfigError::Parse {
+                        path: path.clone(),
+                        line: err.line,
+                        column: err.column,
+                        message: err.message,
+                    },
+                    Err(err) => ConfigError::Read(path.clone(), err),
+                })?;
+
+        migrate(&mut config).map_err(|err| ConfigError::Invalid(path.clone(), err.into()))?;
+
+        let data = config
+            .try_into()
+            .map_err(|err| ConfigError::Invalid(path.clone(), err.into()))?;
+
+        Ok(ConfigSource {
+            path,
+            format,
+            data,
+            changed: false,
+        })
+    }
+
+    pub fn contains(&self, src_path: &Path) -> bool {
+        self.data.files.0.contains_key(src_path) || self.data.dirs.0.contains_key(src_path)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        // the configuration is written to a temporary file first and then
+        // renamed, so that a failed write never leaves a partial config behind
+        let mut temp_file_name = OsString::from(".");
+        temp_file_name.push(self.path.file_name().unwrap_or_default());
+        temp_file_name.push(TEMP_FILE_EXTENSION);
+        let temp_file_path = self.path.with_file_name(temp_file_name);
+
+        let original = read_to_string(&self.path).ok();
+
+        debug!("writing configuration to {:?}", temp_file_path);
+
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let result = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp_file_path)
+            .map_err(|err| err.into())
+            .and_then(|config_file| {
+                let mut writer = BufWriter::new(config_file);
+                self.format
+                    .write(&self.data, original.as_deref(), &mut writer)?;
+                writer.into_inner()?.sync_all()?;
+                Ok(())
+            })
+            .and_then(|_| Ok(rename(&temp_file_path, &self.path)?));
+
+        if result.is_err() {
+            remove_file(&temp_file_path).unwrap_or_default();
+        }
+
+        result
+    }
+}
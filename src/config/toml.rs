@@ -4,9 +4,10 @@ use std::{
 };
 
 use anyhow::Result;
-use toml::{from_str as from_toml_str, to_string_pretty as to_toml_string};
+use toml::{from_str as from_toml_str, to_string_pretty as to_toml_string, Table};
+use toml_edit::{DocumentMut, Item, TableLike, Value};
 
-use super::{ConfigData, ConfigFilePersistence};
+use super::{ConfigData, ConfigFilePersistence, SyntaxError};
 
 const TOML_CONFIG_FILE_NAME: &str = "config.toml";
 
@@ -17,15 +18,165 @@ impl ConfigFilePersistence for TomlConfigFilePersistence {
         PathBuf::from(TOML_CONFIG_FILE_NAME)
     }
 
-    fn read(reader: &mut impl Read) -> Result<ConfigData> {
+    fn read(reader: &mut impl Read) -> Result<Table> {
         let mut toml = String::new();
         reader.read_to_string(&mut toml)?;
-        Ok(from_toml_str(&toml)?)
+
+        from_toml_str(&toml).map_err(|err| {
+            let offset = err.span().map(|s| s.start).unwrap_or_default();
+            SyntaxError::at_offset(&toml, offset, err.message()).into()
+        })
     }
 
-    fn write(data: &ConfigData, writer: &mut impl Write) -> Result<()> {
+    fn write(data: &ConfigData, original: Option<&str>, writer: &mut impl Write) -> Result<()> {
         let toml = to_toml_string(data)?;
+
+        // the existing document is edited in place, so that comments and the
+        // order of untouched keys survive a save
+        let toml = match original.map(|o| o.parse::<DocumentMut>()) {
+            Some(Ok(mut document)) => {
+                let updated = toml.parse::<DocumentMut>()?;
+                merge_table(document.as_table_mut(), table_entries(updated.as_table()));
+                document.to_string()
+            }
+            _ => toml,
+        };
+
         write!(writer, "{}", toml)?;
         Ok(())
     }
 }
+
+fn table_entries(table: &dyn TableLike) -> Vec<(String, Item)> {
+    table
+        .iter()
+        .map(|(key, item)| (key.to_string(), item.clone()))
+        .collect()
+}
+
+fn merge_table(existing: &mut dyn TableLike, updated: Vec<(String, Item)>) {
+    let removed_keys: Vec<_> = existing
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !updated.iter().any(|(k, _)| k == key))
+        .collect();
+
+    for key in removed_keys {
+        existing.remove(&key);
+    }
+
+    for (key, item) in updated {
+        match existing.get_mut(&key) {
+            Some(existing_item) => merge_item(existing_item, item),
+            None => {
+                existing.insert(&key, detach(item));
+            }
+        }
+    }
+}
+
+fn merge_item(existing: &mut Item, updated: Item) {
+    match (existing, updated) {
+        (Item::Table(existing), Item::Table(updated)) => {
+            merge_table(existing, table_entries(&updated))
+        }
+        (Item::Value(Value::InlineTable(existing)), Item::Table(updated)) => {
+            merge_table(existing, table_entries(&updated.into_inline_table()))
+        }
+        (Item::Value(Value::InlineTable(existing)), Item::Value(Value::InlineTable(updated))) => {
+            merge_table(existing, table_entries(&updated))
+        }
+        (Item::Value(existing), Item::Table(updated)) => {
+            // an entry that gains options stays on its own line
+            let mut updated = Value::InlineTable(updated.into_inline_table());
+            *updated.decor_mut() = existing.decor().clone();
+            *existing = updated;
+        }
+        (Item::Value(existing), Item::Value(mut updated)) => {
+            if !same_value(existing, &updated) {
+                *updated.decor_mut() = existing.decor().clone();
+                *existing = updated;
+            }
+        }
+        (existing, updated) => *existing = detach(updated),
+    }
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    let parse = |v: &Value| from_toml_str::<Table>(&format!("v = {}", v)).ok();
+    parse(a) == parse(b)
+}
+
+// tables parsed from the serialized config carry their position in that
+// document, rebuilding them lets new tables follow their parent instead
+fn detach(item: Item) -> Item {
+    match item {
+        Item::Table(table) => {
+            let mut detached = toml_edit::Table::new();
+            detached.set_implicit(table.is_implicit());
+
+            for (key, item) in table {
+                detached.insert(&key, detach(item));
+            }
+
+            Item::Table(detached)
+        }
+        item => item,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::files::path::Paths;
+
+    fn write_to_string(data: &ConfigData, original: &str) -> String {
+        let mut output = vec![];
+        TomlConfigFilePersistence::write(data, Some(original), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_read_reports_location() {
+        let toml = "[files]\n\"~/.zshrc\" = \"home/zshrc\"\n\"~/.vimrc\" = home/vimrc\n";
+
+        let err = TomlConfigFilePersistence::read(&mut toml.as_bytes()).unwrap_err();
+        let err = err.downcast::<SyntaxError>().unwrap();
+
+        assert_eq!(err.line, 3);
+        assert_eq!(err.column, 14);
+    }
+
+    #[test]
+    fn test_write_preserves_comments() {
+        let original = "# my dotfiles\nversion = 1\n\n[dirs]\n\n[files]\n# shell\n\"~/.zshrc\" = \"home/zshrc\" # keep\n\"~/.vimrc\" = \"home/vimrc\"\n";
+        let mut data: ConfigData = from_toml_str(original).unwrap();
+        let paths = Paths::new("/home/user/.twist");
+
+        data.remove_file(Path::new("~/.vimrc"));
+        data.add_file(
+            &paths.resolve_file_paths_from_config_paths("~/.gitconfig", "home/gitconfig"),
+        );
+
+        assert_eq!(
+            write_to_string(&data, original),
+            "# my dotfiles\nversion = 1\n\n[dirs]\n\n[files]\n# shell\n\"~/.zshrc\" = \"home/zshrc\" # keep\n\"~/.gitconfig\" = \"home/gitconfig\"\n"
+        );
+    }
+
+    #[test]
+    fn test_write_keeps_entries_inline() {
+        let original = "version = 1\n\n[files]\n\"~/.zshrc\" = \"home/zshrc\" # shell\n";
+        let mut data: ConfigData = from_toml_str(original).unwrap();
+        let mut options = data.files.0[Path::new("~/.zshrc")].options.clone();
+        options.set("mode", "600").unwrap();
+        data.files.0.get_mut(Path::new("~/.zshrc")).unwrap().options = options;
+
+        assert_eq!(
+            write_to_string(&data, original),
+            "version = 1\n\n[files]\n\"~/.zshrc\" = { path = \"home/zshrc\", mode = \"600\" } # shell\n\n[dirs]\n"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use toml::Table;
+
+use super::{table_from_value, ConfigData, ConfigFilePersistence, SyntaxError};
+
+const YAML_CONFIG_FILE_NAME: &str = "config.yaml";
+
+pub(super) struct YamlConfigFilePersistence;
+
+impl ConfigFilePersistence for YamlConfigFilePersistence {
+    fn file_name() -> PathBuf {
+        PathBuf::from(YAML_CONFIG_FILE_NAME)
+    }
+
+    fn read(reader: &mut impl Read) -> Result<Table> {
+        let mut yaml = String::new();
+        reader.read_to_string(&mut yaml)?;
+
+        let value: Result<serde_json::Value> =
+            serde_norway::from_str(&yaml).map_err(|err| match err.location() {
+                Some(location) => {
+                    // the location is reported separately, so it is dropped from the message
+                    let suffix =
+                        format!(" at line {} column {}", location.line(), location.column());
+                    let message = err.to_string();
+
+                    SyntaxError {
+                        line: location.line(),
+                        column: location.column(),
+                        message: message.replace(&suffix, ""),
+                    }
+                    .into()
+                }
+                None => err.into(),
+            });
+
+        table_from_value(value?)
+    }
+
+    fn write(data: &ConfigData, _original: Option<&str>, writer: &mut impl Write) -> Result<()> {
+        serde_norway::to_writer(writer, data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_reports_location() {
+        let yaml = "files:\n  ~/.zshrc: home/zshrc\n  - home/vimrc\n";
+
+        let err = YamlConfigFilePersistence::read(&mut yaml.as_bytes()).unwrap_err();
+        let err = err.downcast::<SyntaxError>().unwrap();
+
+        assert_eq!(err.line, 3);
+    }
+
+    #[test]
+    fn test_read_reports_null_key() {
+        let yaml = "files:\n  ~/.zshrc: home/zshrc\n  ~/.vimrc:\n";
+
+        let err = YamlConfigFilePersistence::read(&mut yaml.as_bytes()).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "files.~/.vimrc is null, remove it or give it a value"
+        );
+        assert!(YamlConfigFilePersistence::read(&mut "".as_bytes())
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,27 @@
+// well-known configuration paths, grouped by the application that owns them
+pub const CATALOG: [(&str, &[&str]); 24] = [
+    ("sh", &["~/.profile"]),
+    ("bash", &["~/.bashrc", "~/.bash_profile", "~/.bash_aliases"]),
+    ("zsh", &["~/.zshrc", "~/.zshenv", "~/.zprofile"]),
+    ("fish", &["~/.config/fish"]),
+    ("readline", &["~/.inputrc"]),
+    ("git", &["~/.gitconfig", "~/.config/git"]),
+    ("ssh", &["~/.ssh/config"]),
+    ("tmux", &["~/.tmux.conf", "~/.config/tmux"]),
+    ("screen", &["~/.screenrc"]),
+    ("vim", &["~/.vimrc", "~/.vim"]),
+    ("nvim", &["~/.config/nvim"]),
+    ("emacs", &["~/.emacs", "~/.emacs.d/init.el"]),
+    ("helix", &["~/.config/helix"]),
+    ("starship", &["~/.config/starship.toml"]),
+    ("alacritty", &["~/.config/alacritty"]),
+    ("kitty", &["~/.config/kitty"]),
+    ("wezterm", &["~/.wezterm.lua", "~/.config/wezterm"]),
+    ("i3", &["~/.config/i3"]),
+    ("sway", &["~/.config/sway"]),
+    ("htop", &["~/.config/htop"]),
+    ("direnv", &["~/.config/direnv"]),
+    ("editorconfig", &["~/.editorconfig"]),
+    ("curl", &["~/.curlrc"]),
+    ("wget", &["~/.wgetrc"]),
+];
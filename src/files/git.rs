@@ -1,31 +1,51 @@
-use std::fs::{metadata, write};
+use std::ffi::OsStr;
+use std::fs::{create_dir_all, read_to_string, set_permissions, write, Permissions};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use git2::{
     Branch as LibGitBranch, BranchType as LibGitBranchType, Config as LibGitConfig,
     Delta as LibGitDelta, DiffOptions as LibGitDiffOptions, Error as LibGitError,
-    Repository as LibGitRepository, RepositoryInitOptions as LibGitRepositoryInitOptions,
-    Signature as LibGitSignature, Time as LibGitTime,
+    FileMode as LibGitFileMode, ObjectType as LibGitObjectType, Repository as LibGitRepository,
+    RepositoryInitOptions as LibGitRepositoryInitOptions, Signature as LibGitSignature,
+    Status as LibGitStatus, StatusOptions as LibGitStatusOptions, Time as LibGitTime,
+    TreeWalkMode as LibGitTreeWalkMode, TreeWalkResult as LibGitTreeWalkResult,
 };
 
-use log::{debug, info};
+use log::{debug, info, warn};
 use thiserror::Error;
 use time::OffsetDateTime;
 
 use super::path::Paths;
+use crate::config::Settings;
 
 const DEFAULT_COMMITTER_NAME: &str = "Twist";
 const DEFAULT_COMMITTER_EMAIL: &str = "twist@example.com";
 
 const GIT_CONFIG_USER_NAME: &str = "user.name";
 const GIT_CONFIG_USER_EMAIL: &str = "user.email";
+const GIT_CONFIG_CORE_WORKTREE: &str = "core.worktree";
+const GIT_CONFIG_MERGE_DRIVER_NAME: &str = "merge.twist.name";
+const GIT_CONFIG_MERGE_DRIVER: &str = "merge.twist.driver";
+const MERGE_DRIVER_DESCRIPTION: &str = "twist config merge driver";
+// twist is looked up on the PATH when git runs the driver
+const MERGE_DRIVER_COMMAND: &str = "twist merge-config %O %A %B %P";
+const MERGE_DRIVER_ATTRIBUTE: &str = "merge=twist";
+const GIT_ATTRIBUTES_FILE_PATH: &str = "info/attributes";
 const GITIGNORE_FILE_NAME: &str = ".gitignore";
+const EXECUTABLE_FILE_MODE: u32 = 0o755;
 const GITIGNORE_FILE_CONTENT: &str = include_str!("./gitignore.txt");
 
 #[derive(Error, Debug)]
 enum RepositoryError {
     #[error("failed to open repository: {0}")]
     InitializeGit(anyhow::Error),
+    #[error("failed to move repository to {0}: {1}")]
+    Relocate(String, anyhow::Error),
+    #[error("there is no profile named {0}")]
+    UnknownProfile(String),
 }
 
 #[derive(Debug)]
@@ -35,34 +55,34 @@ struct Committer {
 }
 
 impl Committer {
-    fn new(config: &mut LibGitConfig) -> Self {
-        let name = config
-            .get_string(GIT_CONFIG_USER_NAME)
-            .unwrap_or_else(|_| DEFAULT_COMMITTER_NAME.to_string());
-        let email = config
-            .get_string(GIT_CONFIG_USER_EMAIL)
-            .unwrap_or_else(|_| DEFAULT_COMMITTER_EMAIL.to_string());
+    // the twist settings take precedence over the git configuration
+    fn new(config: Option<&mut LibGitConfig>, settings: &Settings) -> Self {
+        let snapshot = config.and_then(|c| c.snapshot().ok());
+        let git_setting = |key| snapshot.as_ref().and_then(|s| s.get_string(key).ok());
+
+        let name = settings
+            .committer_name
+            .clone()
+            .or_else(|| git_setting(GIT_CONFIG_USER_NAME))
+            .unwrap_or_else(|| DEFAULT_COMMITTER_NAME.to_string());
+        let email = settings
+            .committer_email
+            .clone()
+            .or_else(|| git_setting(GIT_CONFIG_USER_EMAIL))
+            .unwrap_or_else(|| DEFAULT_COMMITTER_EMAIL.to_string());
 
         Committer { name, email }
     }
 }
 
-impl Default for Committer {
-    fn default() -> Self {
-        Committer {
-            name: DEFAULT_COMMITTER_NAME.to_string(),
-            email: DEFAULT_COMMITTER_EMAIL.to_string(),
-        }
-    }
-}
-
 pub struct GitRepository {
     repo: LibGitRepository,
     committer: Committer,
+    selected_profile: String,
 }
 
 impl GitRepository {
-    pub fn open(paths: &Paths, profile: &str) -> Result<Self> {
+    pub fn open(paths: &Paths, profile: &str, settings: &Settings) -> Result<Self> {
         let root_dir = &paths.root_dir;
         debug!("opening repository at {:?}", root_dir);
 
@@ -80,42 +100,209 @@ impl GitRepository {
             Err(err) => return Err(RepositoryError::InitializeGit(err.into()).into()),
         };
 
-        let committer = match repo.config() {
-            Ok(mut config) => Committer::new(&mut config),
+        let mut config = match repo.config() {
+            Ok(config) => Some(config),
             Err(err) => {
                 debug!("Error reading git config: {:?}", err);
-                Committer::default()
+                None
             }
         };
+        let committer = Committer::new(config.as_mut(), settings);
         debug!("resolved committer: {:?}", committer);
 
         debug!("successfully opened repository at {:?}", root_dir);
 
-        let repo = Self { repo, committer };
-        repo.switch_profile(profile);
+        let repo = Self {
+            repo,
+            committer,
+            selected_profile: profile.to_string(),
+        };
+        // changes would otherwise be committed to whatever branch is checked out
+        if let Err(err) = repo.switch_profile() {
+            warn!("failed to switch to the profile {}: {}", profile, err);
+        }
 
         let gitignore_file_path = root_dir.join(GITIGNORE_FILE_NAME);
-        if metadata(&gitignore_file_path).is_err() {
-            write(&gitignore_file_path, GITIGNORE_FILE_CONTENT)?
+        match read_to_string(&gitignore_file_path) {
+            Ok(gitignore) => {
+                // repositories created by older versions lack newer patterns
+                let missing_patterns: String = GITIGNORE_FILE_CONTENT
+                    .lines()
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .filter(|l| !gitignore.lines().any(|g| g.trim() == *l))
+                    .map(|l| format!("{}\n", l))
+                    .collect();
+
+                if !missing_patterns.is_empty() {
+                    let separator = if gitignore.ends_with('\n') { "" } else { "\n" };
+                    write(
+                        &gitignore_file_path,
+                        format!("{}{}{}", gitignore, separator, missing_patterns),
+                    )?
+                }
+            }
+            Err(_) => write(&gitignore_file_path, GITIGNORE_FILE_CONTENT)?,
         }
 
         Ok(repo)
     }
 
-    fn switch_profile(&self, profile: &str) {
+    // git merges config files through `twist merge-config`, which merges the
+    // entries instead of the lines, the driver is registered for this clone only
+    pub fn register_merge_driver(&self, patterns: &[String]) -> Result<()> {
+        let mut config = self.repo.config()?;
+        if !self.has_merge_driver_config() {
+            debug!(
+                "registering the config merge driver {:?}",
+                MERGE_DRIVER_COMMAND
+            );
+            config.set_str(GIT_CONFIG_MERGE_DRIVER_NAME, MERGE_DRIVER_DESCRIPTION)?;
+            config.set_str(GIT_CONFIG_MERGE_DRIVER, MERGE_DRIVER_COMMAND)?;
+        }
+
+        let attributes_file_path = self.repo.path().join(GIT_ATTRIBUTES_FILE_PATH);
+        let attributes = read_to_string(&attributes_file_path).unwrap_or_default();
+        let missing_attributes = Self::missing_merge_attributes(&attributes, patterns);
+
+        if !missing_attributes.is_empty() {
+            let separator = if attributes.is_empty() || attributes.ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            create_dir_all(attributes_file_path.parent().unwrap_or(self.repo.path()))?;
+            write(
+                &attributes_file_path,
+                format!("{}{}{}", attributes, separator, missing_attributes),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn has_merge_driver(&self, patterns: &[String]) -> bool {
+        let attributes =
+            read_to_string(self.repo.path().join(GIT_ATTRIBUTES_FILE_PATH)).unwrap_or_default();
+
+        self.has_merge_driver_config()
+            && Self::missing_merge_attributes(&attributes, patterns).is_empty()
+    }
+
+    fn has_merge_driver_config(&self) -> bool {
+        self.repo
+            .config()
+            .and_then(|mut c| c.snapshot())
+            .and_then(|s| s.get_string(GIT_CONFIG_MERGE_DRIVER))
+            .is_ok_and(|driver| driver == MERGE_DRIVER_COMMAND)
+    }
+
+    fn missing_merge_attributes(attributes: &str, patterns: &[String]) -> String {
+        patterns
+            .iter()
+            .map(|pattern| format!("{} {}", pattern, MERGE_DRIVER_ATTRIBUTE))
+            .filter(|line| !attributes.lines().any(|l| l.trim() == line))
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    pub fn exists(root_dir: &Path) -> bool {
+        LibGitRepository::open(root_dir).is_ok()
+    }
+
+    pub fn set_remote(&self, name: &str, url: &str) -> Result<bool> {
+        match self.repo.find_remote(name) {
+            Ok(remote) if remote.url() == Some(url) => return Ok(false),
+            Ok(_) => self.repo.remote_set_url(name, url)?,
+            Err(_) => {
+                self.repo.remote(name, url)?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn relocate(from: &Path, to: &Path) -> Result<()> {
+        debug!("moving repository from {:?} to {:?}", from, to);
+
+        Paths::move_path(from, to)
+            .map_err(|err| RepositoryError::Relocate(to.display().to_string(), err))?;
+
+        let repo = LibGitRepository::open(to)
+            .map_err(|err| RepositoryError::Relocate(to.display().to_string(), err.into()))?;
+        let mut config = repo.config()?;
+
+        if let Ok(work_tree) = config.get_path(GIT_CONFIG_CORE_WORKTREE) {
+            debug!("updating {} from {:?}", GIT_CONFIG_CORE_WORKTREE, work_tree);
+            config.set_str(GIT_CONFIG_CORE_WORKTREE, &to.to_string_lossy())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn profile(&self) -> Option<String> {
+        let head = self.repo.find_reference("HEAD").ok()?;
+        let target = head.symbolic_target()?;
+
+        Some(target.trim_start_matches("refs/heads/").to_string())
+    }
+
+    pub fn selected_profile(&self) -> &str {
+        &self.selected_profile
+    }
+
+    pub fn switch_profile(&self) -> Result<()> {
+        let profile = &self.selected_profile;
         let branch = self.repo.find_branch(profile, LibGitBranchType::Local).ok();
 
-        let result = match branch {
-            Some(branch) => self.switch_branch(&branch),
-            None => self
-                .repo
-                .head()
-                .and_then(|h| h.peel_to_commit())
-                .and_then(|c| self.repo.branch(profile, &c, false))
-                .and_then(|b| self.switch_branch(&b)),
-        };
+        match branch {
+            Some(branch) => self.switch_branch(&branch)?,
+            // without any commits there is nothing to branch from yet
+            None if self.repo.head().is_err() => {
+                self.repo.set_head(&format!("refs/heads/{}", profile))?
+            }
+            None => {
+                let commit = self.repo.head()?.peel_to_commit()?;
+                let branch = self.repo.branch(profile, &commit, false)?;
+                self.switch_branch(&branch)?
+            }
+        }
+
+        Ok(())
+    }
+
+    // the paths, relative to the work tree, that the next commit adds or changes
+    pub fn changed_files(&self) -> Result<Vec<PathBuf>> {
+        let mut status_options = LibGitStatusOptions::new();
+        status_options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true);
+
+        Ok(self
+            .repo
+            .statuses(Some(&mut status_options))?
+            .iter()
+            .filter(|entry| {
+                !entry
+                    .status()
+                    .intersects(LibGitStatus::WT_DELETED | LibGitStatus::INDEX_DELETED)
+            })
+            .filter_map(|entry| entry.path().map(PathBuf::from))
+            .collect())
+    }
+
+    pub fn work_dir(&self) -> Option<&Path> {
+        self.repo.workdir()
+    }
+
+    pub fn has_committed_gitignore(&self) -> Result<bool> {
+        Ok(self
+            .read_committed_file(Path::new(GITIGNORE_FILE_NAME))?
+            .is_some())
+    }
 
-        result.unwrap_or_default();
+    pub fn has_default_committer(&self) -> bool {
+        self.committer.name == DEFAULT_COMMITTER_NAME
+            || self.committer.email == DEFAULT_COMMITTER_EMAIL
     }
 
     fn switch_branch(&self, branch: &LibGitBranch) -> Result<(), LibGitError> {
@@ -124,8 +311,76 @@ impl GitRepository {
         self.repo.set_head(name)
     }
 
+    pub fn read_committed_file(&self, path: &Path) -> Result<Option<Vec<u8>>> {
+        let tree = match self.repo.head().and_then(|h| h.peel_to_tree()) {
+            Ok(tree) => tree,
+            Err(_) => return Ok(None),
+        };
+
+        let entry = match tree.get_path(path) {
+            Ok(entry) => entry,
+            Err(_) => return Ok(None),
+        };
+
+        let blob = entry.to_object(&self.repo)?.peel_to_blob()?;
+
+        Ok(Some(blob.content().to_vec()))
+    }
+
+    // writes the committed files of the profile to dir, leaving HEAD alone
+    pub fn extract_profile(&self, profile: &str, dir: &Path) -> Result<()> {
+        let branch = self
+            .repo
+            .find_branch(profile, LibGitBranchType::Local)
+            .map_err(|_| RepositoryError::UnknownProfile(profile.to_string()))?;
+        let tree = branch.get().peel_to_tree()?;
+
+        let mut blobs = vec![];
+        tree.walk(LibGitTreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() == Some(LibGitObjectType::Blob) {
+                let name = entry.name().unwrap_or_default();
+                blobs.push((Path::new(root).join(name), entry.id(), entry.filemode()));
+            }
+            LibGitTreeWalkResult::Ok
+        })?;
+
+        for (path, id, filemode) in blobs {
+            let full_path = dir.join(path);
+            let blob = self.repo.find_blob(id)?;
+            Paths::ensure_parent_dir(&full_path)?;
+
+            // the content of a symlink blob is its target
+            if filemode == i32::from(LibGitFileMode::Link) {
+                symlink(OsStr::from_bytes(blob.content()), &full_path)?;
+                continue;
+            }
+
+            write(&full_path, blob.content())?;
+            if filemode == i32::from(LibGitFileMode::BlobExecutable) {
+                set_permissions(&full_path, Permissions::from_mode(EXECUTABLE_FILE_MODE))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn reset_index(&self) -> Result<()> {
+        debug!("resetting the index to HEAD");
+        let mut index = self.repo.index()?;
+
+        match self.repo.head().and_then(|h| h.peel_to_tree()) {
+            Ok(tree) => index.read_tree(&tree)?,
+            Err(_) => index.clear()?,
+        }
+
+        index.write()?;
+
+        Ok(())
+    }
+
     pub fn commit(&self, message: &str) -> Result<()> {
-        let now = OffsetDateTime::now_local()?;
+        // the local offset cannot be determined once other threads run
+        let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
         let time = LibGitTime::new(now.unix_timestamp(), now.offset().whole_seconds() / 60);
 
         let head_commit = match self.repo.head() {
@@ -170,6 +425,14 @@ impl GitRepository {
                 | LibGitDelta::Untracked
                 | LibGitDelta::Unmodified => {
                     let path = diff_delta.new_file().path().unwrap();
+                    // nested repositories are reported as untracked directories
+                    if self.work_dir().is_some_and(|w| w.join(path).is_dir()) {
+                        warn!(
+                            "skipping the nested repository {:?}, run `twist doctor` for details",
+                            path
+                        );
+                        continue;
+                    }
                     debug!("Staging {:?} file: {:?}", delta, path);
                     index.add_path(path)?;
                 }
@@ -194,8 +457,6 @@ impl GitRepository {
         self.repo
             .commit(Some("HEAD"), &sig, &sig, message, &index_tree, &parents)?;
 
-        index.clear().unwrap();
-
         Ok(())
     }
 }
@@ -0,0 +1,258 @@
+use std::iter::once;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::path::HOME_DIR_PREFIX;
+
+const HOME_DIR_MAP_NAME: &str = "home";
+const HIDDEN_FILE_PREFIX: &str = ".";
+const CHEZMOI_HIDDEN_FILE_PREFIX: &str = "dot_";
+const UNKNOWN_APP_NAME: &str = "misc";
+const APP_CONTAINER_DIRS: [&str; 9] = [
+    ".config",
+    ".local",
+    "share",
+    "etc",
+    "usr",
+    "opt",
+    "Library",
+    "Application Support",
+    "Preferences",
+];
+const APP_NAME_SUFFIXES: [&str; 4] = ["rc", "config", "_profile", "_aliases"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepoLayout {
+    #[default]
+    Default,
+    KeepDots,
+    Chezmoi,
+    ByApp,
+}
+
+impl RepoLayout {
+    pub fn is_default(&self) -> bool {
+        *self == RepoLayout::Default
+    }
+
+    pub fn repo_path(&self, src_path: &Path) -> PathBuf {
+        match self {
+            RepoLayout::Default => Self::map_components(src_path, |c| {
+                c.strip_prefix(HIDDEN_FILE_PREFIX).unwrap_or(c).to_string()
+            }),
+            RepoLayout::KeepDots => Self::map_components(src_path, |c| c.to_string()),
+            RepoLayout::Chezmoi => {
+                Self::map_components(src_path, |c| match c.strip_prefix(HIDDEN_FILE_PREFIX) {
+                    Some(c) => format!("{}{}", CHEZMOI_HIDDEN_FILE_PREFIX, c),
+                    None => c.to_string(),
+                })
+            }
+            RepoLayout::ByApp => {
+                Path::new(&Self::app_name(src_path)).join(RepoLayout::Default.repo_path(src_path))
+            }
+        }
+    }
+
+    // the inverse of repo_path for paths below the home directory, layouts
+    // that strip leading dots make the first component map to two paths
+    pub fn src_paths(&self, repo_path: &Path) -> Vec<PathBuf> {
+        let mut components = repo_path.components().filter_map(|c| match c {
+            Component::Normal(c) => c.to_str(),
+            _ => None,
+        });
+
+        if *self == RepoLayout::ByApp {
+            components.next();
+        }
+
+        if components.next() != Some(HOME_DIR_MAP_NAME) {
+            return vec![];
+        }
+
+        let unmap = |c: &str| match self {
+            RepoLayout::Chezmoi => match c.strip_prefix(CHEZMOI_HIDDEN_FILE_PREFIX) {
+                Some(c) => format!("{}{}", HIDDEN_FILE_PREFIX, c),
+                None => c.to_string(),
+            },
+            _ => c.to_string(),
+        };
+
+        let components: Vec<_> = components.map(unmap).collect();
+        let (first, rest) = match components.split_first() {
+            Some(split) => split,
+            None => return vec![],
+        };
+        let src_path = |first: &str| -> PathBuf {
+            once(HOME_DIR_PREFIX)
+                .chain(once(first))
+                .chain(rest.iter().map(String::as_str))
+                .collect()
+        };
+
+        match self {
+            RepoLayout::Default | RepoLayout::ByApp if !first.starts_with(HIDDEN_FILE_PREFIX) => {
+                vec![
+                    src_path(&format!("{}{}", HIDDEN_FILE_PREFIX, first)),
+                    src_path(first),
+                ]
+            }
+            _ => vec![src_path(first)],
+        }
+    }
+
+    fn map_components(src_path: &Path, map: impl Fn(&str) -> String) -> PathBuf {
+        let mut path = PathBuf::new();
+
+        for p in src_path.components() {
+            match p {
+                Component::Normal(c) if c == HOME_DIR_PREFIX => {
+                    path.push(HOME_DIR_MAP_NAME);
+                }
+                Component::Normal(c) => match c.to_str() {
+                    Some(c) => {
+                        path.push(map(c));
+                    }
+                    None => {
+                        panic!("invalid path component: {:?}", c);
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        path
+    }
+
+    fn app_name(src_path: &Path) -> String {
+        let name = src_path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(c) if c != HOME_DIR_PREFIX => c.to_str(),
+                _ => None,
+            })
+            .find(|c| !APP_CONTAINER_DIRS.contains(c))
+            .map(|c| c.strip_prefix(HIDDEN_FILE_PREFIX).unwrap_or(c))
+            .and_then(|c| c.split('.').next())
+            .unwrap_or_default();
+
+        let name = APP_NAME_SUFFIXES
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix).filter(|n| !n.is_empty()))
+            .unwrap_or(name);
+
+        if name.is_empty() {
+            UNKNOWN_APP_NAME.to_string()
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_repo_path() {
+        let layout = RepoLayout::Default;
+
+        assert_eq!(
+            layout.repo_path(Path::new("~/.zshrc")),
+            PathBuf::from("home/zshrc")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("~/.config/starship.toml")),
+            PathBuf::from("home/config/starship.toml")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("/etc/nginx/nginx.conf")),
+            PathBuf::from("etc/nginx/nginx.conf")
+        );
+    }
+
+    #[test]
+    fn test_keep_dots_repo_path() {
+        let layout = RepoLayout::KeepDots;
+
+        assert_eq!(
+            layout.repo_path(Path::new("~/.zshrc")),
+            PathBuf::from("home/.zshrc")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("~/.config/starship.toml")),
+            PathBuf::from("home/.config/starship.toml")
+        );
+    }
+
+    #[test]
+    fn test_chezmoi_repo_path() {
+        let layout = RepoLayout::Chezmoi;
+
+        assert_eq!(
+            layout.repo_path(Path::new("~/.zshrc")),
+            PathBuf::from("home/dot_zshrc")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("~/.config/nvim/init.lua")),
+            PathBuf::from("home/dot_config/nvim/init.lua")
+        );
+    }
+
+    #[test]
+    fn test_by_app_repo_path() {
+        let layout = RepoLayout::ByApp;
+
+        assert_eq!(
+            layout.repo_path(Path::new("~/.zshrc")),
+            PathBuf::from("zsh/home/zshrc")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("~/.gitconfig")),
+            PathBuf::from("git/home/gitconfig")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("~/.config/nvim/init.lua")),
+            PathBuf::from("nvim/home/config/nvim/init.lua")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("~/.config/starship.toml")),
+            PathBuf::from("starship/home/config/starship.toml")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("/etc/nginx/nginx.conf")),
+            PathBuf::from("nginx/etc/nginx/nginx.conf")
+        );
+        assert_eq!(
+            layout.repo_path(Path::new("~/.profile")),
+            PathBuf::from("profile/home/profile")
+        );
+    }
+
+    #[test]
+    fn test_src_paths() {
+        assert_eq!(
+            RepoLayout::Default.src_paths(Path::new("home/zshrc")),
+            vec![PathBuf::from("~/.zshrc"), PathBuf::from("~/zshrc")]
+        );
+        assert_eq!(
+            RepoLayout::KeepDots.src_paths(Path::new("home/.config/nvim/init.lua")),
+            vec![PathBuf::from("~/.config/nvim/init.lua")]
+        );
+        assert_eq!(
+            RepoLayout::Chezmoi.src_paths(Path::new("home/dot_config/nvim/dot_init.lua")),
+            vec![PathBuf::from("~/.config/nvim/.init.lua")]
+        );
+        assert_eq!(
+            RepoLayout::ByApp.src_paths(Path::new("nvim/home/config/nvim/init.lua")),
+            vec![
+                PathBuf::from("~/.config/nvim/init.lua"),
+                PathBuf::from("~/config/nvim/init.lua")
+            ]
+        );
+        assert!(RepoLayout::Default
+            .src_paths(Path::new("README.md"))
+            .is_empty());
+    }
+}
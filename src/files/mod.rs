@@ -1,53 +1,446 @@
+pub mod catalog;
 pub mod git;
+pub mod layout;
 pub mod path;
-
-use std::fs::{copy, remove_dir_all, remove_file};
-
-use std::path::PathBuf;
+pub mod preset;
+pub mod template;
+pub mod transaction;
+pub mod validate;
+
+use std::fs::{
+    copy, create_dir_all, metadata, read, read_dir, read_link, remove_dir, remove_dir_all,
+    remove_file, rename, symlink_metadata,
+};
+use std::os::unix::fs::symlink;
+
+use std::collections::BTreeSet;
+use std::path::{absolute, Path, PathBuf};
 use std::rc::Rc;
+use std::slice::from_ref;
 
 use anyhow::{Ok, Result};
 
-use dircpy::copy_dir;
-use log::debug;
+use glob::Pattern;
+use log::{debug, error, info, warn};
+use thiserror::Error;
 
-use crate::config::ConfigManager;
+use crate::config::{ConfigManager, EntryOptions, OverlapPolicy};
 
+use self::catalog::CATALOG;
+use self::layout::RepoLayout;
 use self::path::*;
+use self::transaction::Transaction;
+use self::validate::Validator;
+
+const RELAYOUT_STAGING_DIR_NAME: &str = ".relayout";
+
+#[derive(Error, Debug)]
+enum FileManagerError {
+    #[error("more than one entry would be stored at {0} with the new layout")]
+    LayoutConflict(PathBuf),
+    #[error("{0} overlaps with the tracked entry {1}, use --on-overlap to merge or override it")]
+    OverlappingEntry(PathBuf, PathBuf),
+    #[error("the following paths are not tracked: {0}")]
+    UntrackedPaths(String),
+    #[error("{0} matches more than one tracked entry")]
+    AmbiguousPath(PathBuf),
+    #[error("{0} already exists, use --repo-only if it was already moved")]
+    DestinationExists(PathBuf),
+    #[error("{0} is inside of the root directory")]
+    InsideRootDir(PathBuf),
+    #[error("{0} files failed validation, fix them or pass --no-verify to commit anyway")]
+    InvalidFiles(usize),
+}
+
+#[derive(Debug)]
+pub struct TrackedPath {
+    pub entry_src_path: PathBuf,
+    pub entry_is_dir: bool,
+    pub full_src_path: PathBuf,
+    pub repo_path: PathBuf,
+    pub full_repo_path: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SyncStatus {
+    Matches,
+    Differs,
+    MissingFromRepo,
+    MissingFromSystem,
+}
 
 pub struct FileManager {
     paths: Rc<Paths>,
     config: Rc<ConfigManager>,
+    transaction: Rc<Transaction>,
 }
 
 impl FileManager {
-    pub fn new(config: &Rc<ConfigManager>, paths: &Rc<Paths>) -> Self {
+    pub fn new(
+        config: &Rc<ConfigManager>,
+        paths: &Rc<Paths>,
+        transaction: &Rc<Transaction>,
+    ) -> Self {
         FileManager {
             config: config.clone(),
             paths: paths.clone(),
+            transaction: transaction.clone(),
         }
     }
 
-    pub fn add(&self, paths: &[PathBuf]) -> Result<()> {
+    pub fn add(&self, paths: &[PathBuf], overlap_policy: OverlapPolicy) -> Result<()> {
+        self.add_with_options(paths, overlap_policy, &EntryOptions::default())
+    }
+
+    pub fn add_with_options(
+        &self,
+        paths: &[PathBuf],
+        overlap_policy: OverlapPolicy,
+        options: &EntryOptions,
+    ) -> Result<()> {
         let (files, dirs) = self.paths.resolve_paths(paths);
 
-        self.add_files(&files)?;
-        self.add_dirs(&dirs)?;
+        // entries are added one at a time so that overlaps between the given
+        // paths are detected as well
+        for file in files {
+            if self.resolve_overlaps(&file.src_path, overlap_policy)? {
+                self.add_files(from_ref(&file), options)?;
+            }
+        }
+
+        for dir in dirs {
+            if self.resolve_overlaps(&dir.src_path, overlap_policy)? {
+                self.add_dirs(from_ref(&dir), options)?;
+            }
+        }
 
         Ok(())
     }
 
-    pub fn update(&self) -> Result<()> {
+    fn resolve_overlaps(&self, src_path: &Path, overlap_policy: OverlapPolicy) -> Result<bool> {
+        let containing_dirs = self.config.containing_dirs(src_path);
+        let (contained_files, contained_dirs) = self.config.contained_entries(src_path);
+
+        let overlapping_path = containing_dirs
+            .iter()
+            .map(|d| &d.src_path)
+            .chain(contained_files.iter().map(|f| &f.src_path))
+            .chain(contained_dirs.iter().map(|d| &d.src_path))
+            .next();
+
+        let overlapping_path = match overlapping_path {
+            Some(overlapping_path) => overlapping_path,
+            None => return Ok(true),
+        };
+
+        match overlap_policy {
+            OverlapPolicy::Refuse => Err(FileManagerError::OverlappingEntry(
+                src_path.to_path_buf(),
+                overlapping_path.clone(),
+            )
+            .into()),
+            OverlapPolicy::Merge if !containing_dirs.is_empty() => {
+                info!(
+                    "{:?} is already tracked as part of {:?}",
+                    src_path, containing_dirs[0].src_path
+                );
+                Ok(false)
+            }
+            OverlapPolicy::Merge => {
+                info!("merging the entries inside {:?}", src_path);
+                self.remove_files(&contained_files)?;
+                self.remove_dirs(&contained_dirs)?;
+                Ok(true)
+            }
+            OverlapPolicy::Override => {
+                for dir in &containing_dirs {
+                    self.config.add_override(src_path, &dir.src_path);
+
+                    // the directory copy must not hold a second copy of the entry
+                    let nested_repo_path = dir
+                        .full_repo_path
+                        .join(src_path.strip_prefix(&dir.src_path)?);
+                    self.transaction.track(&nested_repo_path)?;
+                    match metadata(&nested_repo_path).map(|m| m.is_dir()).ok() {
+                        Some(true) => remove_dir_all(&nested_repo_path)?,
+                        Some(false) => remove_file(&nested_repo_path)?,
+                        None => {}
+                    }
+                }
+
+                for nested_path in contained_files
+                    .iter()
+                    .map(|f| &f.src_path)
+                    .chain(contained_dirs.iter().map(|d| &d.src_path))
+                {
+                    self.config.add_override(nested_path, src_path);
+                }
+
+                Ok(true)
+            }
+        }
+    }
+
+    // the catalog paths that exist on this system and are not tracked yet
+    pub fn discover(&self) -> Vec<(&'static str, PathBuf)> {
+        CATALOG
+            .iter()
+            .flat_map(|(app, paths)| paths.iter().map(move |p| (*app, Path::new(p))))
+            .map(|(app, path)| (app, self.paths.full_src_path(path)))
+            .filter(|(_, path)| metadata(path).is_ok() && self.which(path).is_none())
+            .collect()
+    }
+
+    pub fn validate(&self, repo_paths: &[PathBuf]) -> Result<()> {
         let files = self.config.files();
+        let dirs = self.config.dirs();
+        let mut invalid_count = 0;
+
+        for repo_path in repo_paths {
+            let full_repo_path = self.paths.root_dir().join(repo_path);
+            let options = files
+                .iter()
+                .find(|f| f.full_repo_path == full_repo_path)
+                .map(|f| &f.src_path)
+                .or_else(|| {
+                    dirs.iter()
+                        .find(|d| full_repo_path.starts_with(&d.full_repo_path))
+                        .map(|d| &d.src_path)
+                })
+                .and_then(|src_path| self.config.entry_options(src_path))
+                .unwrap_or_default();
+
+            // templates and encrypted files are only valid once they are applied
+            if options.template || options.encryption.is_some() {
+                continue;
+            }
+
+            let Some(validator) = Validator::for_path(repo_path, options.validate.as_deref())
+            else {
+                continue;
+            };
+
+            debug!("validating {:?} with {:?}", repo_path, validator);
+            if let Err(message) = validator.validate(&full_repo_path) {
+                error!("{:?} is not valid: {}", repo_path, message);
+                invalid_count += 1;
+            }
+        }
+
+        if invalid_count > 0 {
+            return Err(FileManagerError::InvalidFiles(invalid_count).into());
+        }
+
+        Ok(())
+    }
+
+    pub fn which(&self, path: &Path) -> Option<TrackedPath> {
+        let file = self.paths.resolve_file_paths(path)?;
+
+        if self.config.contains_file(&file) {
+            let file = self
+                .config
+                .files()
+                .into_iter()
+                .find(|f| f.src_path == file.src_path)?;
+
+            return Some(TrackedPath {
+                entry_src_path: file.src_path,
+                entry_is_dir: false,
+                full_src_path: file.full_src_path,
+                repo_path: file.repo_path,
+                full_repo_path: file.full_repo_path,
+            });
+        }
+
+        let dir = self.paths.resolve_dir_paths(path)?;
+
+        if self.config.contains_dir(&dir) {
+            let dir = self
+                .config
+                .dirs()
+                .into_iter()
+                .find(|d| d.src_path == dir.src_path)?;
+
+            return Some(TrackedPath {
+                entry_src_path: dir.src_path,
+                entry_is_dir: true,
+                full_src_path: dir.full_src_path,
+                repo_path: dir.repo_path,
+                full_repo_path: dir.full_repo_path,
+            });
+        }
+
+        // the innermost directory entry is the one that owns the path
+        let dir = self
+            .config
+            .containing_dirs(&dir.src_path)
+            .into_iter()
+            .max_by_key(|d| d.src_path.components().count())?;
+        let relative_path = file.src_path.strip_prefix(&dir.src_path).ok()?;
+
+        Some(TrackedPath {
+            entry_src_path: dir.src_path,
+            entry_is_dir: true,
+            full_src_path: file.full_src_path,
+            repo_path: dir.repo_path.join(relative_path),
+            full_repo_path: dir.full_repo_path.join(relative_path),
+        })
+    }
+
+    pub fn compare(system_path: &Path, repo_path: &Path) -> Result<SyncStatus> {
+        let status = match (metadata(system_path), metadata(repo_path)) {
+            (Err(_), _) => SyncStatus::MissingFromSystem,
+            (_, Err(_)) => SyncStatus::MissingFromRepo,
+            _ if Self::same_contents(system_path, repo_path)? => SyncStatus::Matches,
+            _ => SyncStatus::Differs,
+        };
+
+        Ok(status)
+    }
+
+    fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+        match (metadata(a)?.is_dir(), metadata(b)?.is_dir()) {
+            (false, false) => Ok(read(a)? == read(b)?),
+            (true, true) => {
+                let names = |p: &Path| -> Result<BTreeSet<_>> {
+                    Ok(read_dir(p)?
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.file_name())
+                        .collect())
+                };
+
+                let a_names = names(a)?;
+                if a_names != names(b)? {
+                    return Ok(false);
+                }
+
+                for name in a_names {
+                    if !Self::same_contents(&a.join(&name), &b.join(&name))? {
+                        return Ok(false);
+                    }
+                }
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    pub fn update(&self) -> Result<()> {
+        let is_enabled = |src_path: &Path| {
+            let is_disabled = self.config.is_disabled(src_path);
+            if is_disabled {
+                debug!("skipping {:?}, it is disabled locally", src_path);
+            }
+            !is_disabled
+        };
+
+        let files: Vec<_> = self
+            .config
+            .files()
+            .into_iter()
+            .filter(|f| is_enabled(&f.src_path))
+            .collect();
         self.copy_files_to_repo(&files)?;
 
-        let dirs = self.config.dirs();
+        let dirs: Vec<_> = self
+            .config
+            .dirs()
+            .into_iter()
+            .filter(|d| is_enabled(&d.src_path))
+            .collect();
         self.copy_dirs_to_repo(&dirs)?;
         Ok(())
     }
 
+    pub fn missing_from_repo(&self) -> (Vec<FilePathInfo>, Vec<DirPathInfo>) {
+        let files = self
+            .config
+            .files()
+            .into_iter()
+            .filter(|f| metadata(&f.full_repo_path).is_err())
+            .collect();
+        let dirs = self
+            .config
+            .dirs()
+            .into_iter()
+            .filter(|d| metadata(&d.full_repo_path).is_err())
+            .collect();
+
+        (files, dirs)
+    }
+
+    pub fn copy_to_repo(&self, files: &[FilePathInfo], dirs: &[DirPathInfo]) -> Result<()> {
+        self.copy_files_to_repo(files)?;
+        self.copy_dirs_to_repo(dirs)
+    }
+
+    pub fn orphans(&self) -> Result<Vec<PathBuf>> {
+        let files: BTreeSet<_> = self
+            .config
+            .files()
+            .into_iter()
+            .map(|f| f.full_repo_path)
+            .collect();
+        let dirs: Vec<_> = self
+            .config
+            .dirs()
+            .into_iter()
+            .map(|d| d.full_repo_path)
+            .collect();
+
+        let mut orphans = vec![];
+        if metadata(&self.paths.files_dir).is_ok() {
+            Self::collect_orphans(&self.paths.files_dir, &files, &dirs, &mut orphans)?;
+        }
+        orphans.sort();
+
+        Ok(orphans)
+    }
+
+    pub fn remove_orphans(&self, orphans: &[PathBuf]) -> Result<()> {
+        for orphan in orphans {
+            debug!("removing orphaned file {:?}", orphan);
+            self.transaction.track(orphan)?;
+            remove_file(orphan)?;
+            self.remove_empty_parent_dirs(orphan)?;
+        }
+
+        Ok(())
+    }
+
+    fn collect_orphans(
+        dir: &Path,
+        files: &BTreeSet<PathBuf>,
+        dirs: &[PathBuf],
+        orphans: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+
+            // a nested repository is reported by itself, not file by file
+            if files.contains(&path)
+                || dirs.iter().any(|d| path.starts_with(d))
+                || path.ends_with(GIT_DIR_NAME)
+            {
+                continue;
+            }
+
+            if metadata(&path)?.is_dir() {
+                Self::collect_orphans(&path, files, dirs, orphans)?;
+            } else {
+                orphans.push(path);
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn remove(&self, paths: &[PathBuf]) -> Result<()> {
-        let (files, dirs) = self.paths.resolve_paths(paths);
+        let (files, dirs) = self.match_entries(paths)?;
+
+        debug!("matched {} files and {} dirs", files.len(), dirs.len());
 
         self.remove_files(&files)?;
         self.remove_dirs(&dirs)?;
@@ -55,10 +448,274 @@ impl FileManager {
         Ok(())
     }
 
-    fn add_files(&self, files: &[FilePathInfo]) -> Result<()> {
+    pub fn move_entry(&self, old_path: &Path, new_path: &Path, repo_only: bool) -> Result<()> {
+        let (files, dirs) = self.match_entries(&[old_path.to_path_buf()])?;
+        let new_path = absolute(new_path)?;
+
+        if files.len() + dirs.len() > 1 {
+            return Err(FileManagerError::AmbiguousPath(old_path.to_path_buf()).into());
+        }
+
+        if repo_only && metadata(&new_path).is_err() {
+            warn!("{:?} does not exist on the system", new_path);
+        }
+
+        if !repo_only && metadata(&new_path).is_ok() {
+            return Err(FileManagerError::DestinationExists(new_path).into());
+        }
+
+        let ((old_full_src_path, old_full_repo_path), (new_full_src_path, new_full_repo_path)) =
+            match (files.into_iter().next(), dirs.into_iter().next()) {
+                (Some(old), _) => {
+                    let new = self
+                        .paths
+                        .resolve_file_paths(&new_path)
+                        .ok_or_else(|| FileManagerError::InsideRootDir(new_path.clone()))?;
+                    self.resolve_overlaps(&new.src_path, OverlapPolicy::Refuse)?;
+
+                    // the moved entry stays in the configuration file that defined it
+                    let options = self.config.entry_options(&old.src_path).unwrap_or_default();
+                    self.config.set_target_entry(&old.src_path);
+                    self.config.remove_files(from_ref(&old));
+                    self.config.add_files(from_ref(&new));
+                    self.config.set_entry_options(&new.src_path, options)?;
+
+                    (
+                        (old.full_src_path, old.full_repo_path),
+                        (new.full_src_path, new.full_repo_path),
+                    )
+                }
+                (_, Some(old)) => {
+                    let new = self
+                        .paths
+                        .resolve_dir_paths(&new_path)
+                        .ok_or_else(|| FileManagerError::InsideRootDir(new_path.clone()))?;
+                    self.resolve_overlaps(&new.src_path, OverlapPolicy::Refuse)?;
+
+                    let options = self.config.entry_options(&old.src_path).unwrap_or_default();
+                    self.config.set_target_entry(&old.src_path);
+                    self.config.remove_dirs(from_ref(&old));
+                    self.config.add_dirs(from_ref(&new));
+                    self.config.set_entry_options(&new.src_path, options)?;
+
+                    (
+                        (old.full_src_path, old.full_repo_path),
+                        (new.full_src_path, new.full_repo_path),
+                    )
+                }
+                _ => unreachable!("matched entries are never empty"),
+            };
+
+        if !repo_only {
+            debug!("moving {:?} to {:?}", old_full_src_path, new_full_src_path);
+            self.transaction.track(&old_full_src_path)?;
+            self.transaction.track(&new_full_src_path)?;
+            Paths::ensure_parent_dir(&new_full_src_path)?;
+            Paths::move_path(&old_full_src_path, &new_full_src_path)?;
+        }
+
+        if old_full_repo_path != new_full_repo_path {
+            debug!(
+                "moving {:?} to {:?}",
+                old_full_repo_path, new_full_repo_path
+            );
+            self.transaction.track(&old_full_repo_path)?;
+            self.transaction.track(&new_full_repo_path)?;
+            Paths::ensure_parent_dir(&new_full_repo_path)?;
+            rename(&old_full_repo_path, &new_full_repo_path)?;
+            self.remove_empty_parent_dirs(&old_full_repo_path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn match_entry(&self, path: &Path) -> Result<PathBuf> {
+        let (files, dirs) = self.match_entries(&[path.to_path_buf()])?;
+
+        match (files.as_slice(), dirs.as_slice()) {
+            ([file], []) => Ok(file.src_path.clone()),
+            ([], [dir]) => Ok(dir.src_path.clone()),
+            _ => Err(FileManagerError::AmbiguousPath(path.to_path_buf()).into()),
+        }
+    }
+
+    fn match_entries(&self, paths: &[PathBuf]) -> Result<(Vec<FilePathInfo>, Vec<DirPathInfo>)> {
+        let tracked_files = self.config.files();
+        let tracked_dirs = self.config.dirs();
+
+        let mut matched_src_paths = BTreeSet::new();
+        let mut untracked_paths = vec![];
+
+        // paths are matched against the entries instead of the file system, so
+        // that entries can be found after their source has been deleted or moved
+        for p in paths {
+            let full_path = absolute(p)?;
+            let is_match = |src_path: &Path, full_src_path: &Path, config_repo_path: &Path| {
+                let full_repo_path = self.paths.files_dir.join(config_repo_path);
+                let repo_path = full_repo_path.strip_prefix(&self.paths.root_dir).ok();
+
+                p == src_path
+                    || full_path == full_src_path
+                    || p == config_repo_path
+                    || Some(p.as_path()) == repo_path
+                    || full_path == full_repo_path
+            };
+
+            let matched: Vec<_> = tracked_files
+                .iter()
+                .filter(|f| is_match(&f.src_path, &f.full_src_path, &f.config_repo_path))
+                .map(|f| f.src_path.clone())
+                .chain(
+                    tracked_dirs
+                        .iter()
+                        .filter(|d| is_match(&d.src_path, &d.full_src_path, &d.config_repo_path))
+                        .map(|d| d.src_path.clone()),
+                )
+                .collect();
+
+            if matched.is_empty() {
+                untracked_paths.push(p.display().to_string());
+            }
+
+            matched_src_paths.extend(matched);
+        }
+
+        if !untracked_paths.is_empty() {
+            return Err(FileManagerError::UntrackedPaths(untracked_paths.join(", ")).into());
+        }
+
+        let files = tracked_files
+            .into_iter()
+            .filter(|f| matched_src_paths.contains(&f.src_path))
+            .collect();
+        let dirs = tracked_dirs
+            .into_iter()
+            .filter(|d| matched_src_paths.contains(&d.src_path))
+            .collect();
+
+        Ok((files, dirs))
+    }
+
+    pub fn relayout(&self, layout: RepoLayout) -> Result<()> {
+        let files = self.config.files();
+        let dirs = self.config.dirs();
+
+        self.paths.set_layout(layout);
+
+        let new_files: Vec<_> = files
+            .iter()
+            .map(|f| {
+                self.paths.resolve_file_paths_from_config_paths(
+                    &f.src_path,
+                    &self.paths.repo_path(&f.src_path),
+                )
+            })
+            .collect();
+        let new_dirs: Vec<_> = dirs
+            .iter()
+            .map(|d| {
+                self.paths.resolve_dir_paths_from_config_paths(
+                    &d.src_path,
+                    &self.paths.repo_path(&d.src_path),
+                )
+            })
+            .collect();
+
+        let mut config_repo_paths = BTreeSet::new();
+        for path in new_files
+            .iter()
+            .map(|f| &f.config_repo_path)
+            .chain(new_dirs.iter().map(|d| &d.config_repo_path))
+        {
+            if !config_repo_paths.insert(path) {
+                return Err(FileManagerError::LayoutConflict(path.clone()).into());
+            }
+        }
+
+        let moves: Vec<_> = files
+            .iter()
+            .zip(&new_files)
+            .map(|(old, new)| (&old.full_repo_path, &new.full_repo_path))
+            .chain(
+                dirs.iter()
+                    .zip(&new_dirs)
+                    .map(|(old, new)| (&old.full_repo_path, &new.full_repo_path)),
+            )
+            .filter(|(old, new)| old != new)
+            .collect();
+
+        self.move_repo_paths(&moves)?;
+
+        self.config.set_layout(layout);
+        self.config.add_files(&new_files);
+        self.config.add_dirs(&new_dirs);
+
+        Ok(())
+    }
+
+    fn move_repo_paths(&self, moves: &[(&PathBuf, &PathBuf)]) -> Result<()> {
+        // moving through a staging directory avoids clobbering entries whose
+        // new path is the old path of another entry
+        let staging_dir = self.paths.root_dir.join(RELAYOUT_STAGING_DIR_NAME);
+        self.transaction.track(&staging_dir)?;
+        create_dir_all(&staging_dir)?;
+
+        let mut staged = vec![];
+
+        for (i, (old, new)) in moves.iter().enumerate() {
+            if metadata(old).is_err() {
+                warn!("{:?} is missing from the repository, skipping", old);
+                continue;
+            }
+
+            let staging_path = staging_dir.join(i.to_string());
+            debug!("moving {:?} to {:?}", old, staging_path);
+            self.transaction.track(old)?;
+            self.transaction.track(new)?;
+            rename(old, &staging_path)?;
+            self.remove_empty_parent_dirs(old)?;
+
+            staged.push((staging_path, new));
+        }
+
+        for (staging_path, new) in staged {
+            debug!("moving {:?} to {:?}", staging_path, new);
+            Paths::ensure_parent_dir(new)?;
+            rename(&staging_path, new)?;
+        }
+
+        remove_dir(&staging_dir)?;
+
+        Ok(())
+    }
+
+    fn remove_empty_parent_dirs(&self, path: &Path) -> Result<()> {
+        let mut parent = path.parent();
+
+        while let Some(dir) = parent.filter(|p| p.starts_with(&self.paths.files_dir)) {
+            if dir == self.paths.files_dir || read_dir(dir)?.next().is_some() {
+                break;
+            }
+
+            debug!("removing empty directory {:?}", dir);
+            remove_dir(dir)?;
+            parent = dir.parent();
+        }
+
+        Ok(())
+    }
+
+    fn add_files(&self, files: &[FilePathInfo], options: &EntryOptions) -> Result<()> {
         if !files.is_empty() {
             self.copy_files_to_repo(files)?;
             self.config.add_files(files);
+
+            // excludes only make sense for directories
+            let options = EntryOptions {
+                excludes: Vec::new(),
+                ..options.clone()
+            };
+            self.extend_entry_options(files.iter().map(|f| &f.src_path), &options)?;
         }
 
         Ok(())
@@ -66,27 +723,47 @@ impl FileManager {
 
     fn copy_files_to_repo(&self, files: &[FilePathInfo]) -> Result<()> {
         for file in files {
-            Paths::ensure_parent_dir(&file.full_repo_path)?;
-
             debug!(
                 "copying file {:?} to {:?}",
                 file.full_src_path, file.full_repo_path
             );
 
+            self.transaction.track(&file.full_repo_path)?;
+            Paths::ensure_parent_dir(&file.full_repo_path)?;
             copy(&file.full_src_path, &file.full_repo_path)?;
         }
 
         Ok(())
     }
 
-    fn add_dirs(&self, dirs: &[DirPathInfo]) -> Result<()> {
+    fn add_dirs(&self, dirs: &[DirPathInfo], options: &EntryOptions) -> Result<()> {
         if !dirs.is_empty() {
-            self.copy_dirs_to_repo(dirs)?;
+            // the options are set before copying so that the excludes apply
             self.config.add_dirs(dirs);
+            self.extend_entry_options(dirs.iter().map(|d| &d.src_path), options)?;
+            self.copy_dirs_to_repo(dirs)?;
         }
         Ok(())
     }
 
+    fn extend_entry_options<'a>(
+        &self,
+        src_paths: impl Iterator<Item = &'a PathBuf>,
+        options: &EntryOptions,
+    ) -> Result<()> {
+        if options.is_empty() {
+            return Ok(());
+        }
+
+        for src_path in src_paths {
+            let mut entry_options = self.config.entry_options(src_path).unwrap_or_default();
+            entry_options.extend(options);
+            self.config.set_entry_options(src_path, entry_options)?;
+        }
+
+        Ok(())
+    }
+
     fn copy_dirs_to_repo(&self, dirs: &[DirPathInfo]) -> Result<()> {
         for dir in dirs {
             debug!(
@@ -94,8 +771,87 @@ impl FileManager {
                 dir.full_src_path, dir.repo_path
             );
 
+            let overrides: Vec<_> = self
+                .config
+                .overrides_in(&dir.src_path)
+                .iter()
+                .map(|p| self.paths.full_src_path(p))
+                .collect();
+
+            self.transaction.track(&dir.full_repo_path)?;
             Paths::ensure_parent_dir(&dir.full_repo_path)?;
-            copy_dir(&dir.full_src_path, &dir.full_repo_path)?;
+            Self::copy_dir_skipping(&dir.full_src_path, &dir.full_repo_path, &overrides)?;
+
+            let excludes = self
+                .config
+                .entry_options(&dir.src_path)
+                .map(|o| o.excludes)
+                .unwrap_or_default()
+                .iter()
+                .map(|e| Pattern::new(e))
+                .collect::<Result<Vec<_>, _>>()?;
+            Self::remove_excluded(&dir.full_repo_path, &dir.full_repo_path, &excludes)?;
+        }
+
+        Ok(())
+    }
+
+    // like dircpy, existing files are kept and symlinks are copied as links,
+    // the skipped paths are compared by component and not as substrings
+    fn copy_dir_skipping(from: &Path, to: &Path, skipped: &[PathBuf]) -> Result<()> {
+        create_dir_all(to)?;
+
+        for entry in read_dir(from)? {
+            let entry = entry?;
+            let src_path = entry.path();
+            if skipped.iter().any(|p| src_path.starts_with(p)) {
+                debug!("skipping {:?}, it is tracked on its own", src_path);
+                continue;
+            }
+
+            let dest_path = to.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                Self::copy_dir_skipping(&src_path, &dest_path, skipped)?;
+            } else if symlink_metadata(&dest_path).is_ok() {
+                continue;
+            } else if file_type.is_symlink() {
+                symlink(read_link(&src_path)?, &dest_path)?;
+            } else {
+                copy(&src_path, &dest_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_excluded(base_dir: &Path, dir: &Path, excludes: &[Pattern]) -> Result<()> {
+        if excludes.is_empty() {
+            return Ok(());
+        }
+
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+            let relative_path = path.strip_prefix(base_dir)?;
+            let is_excluded = excludes.iter().any(|pattern| {
+                pattern.matches_path(relative_path)
+                    || path
+                        .file_name()
+                        .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+            });
+
+            match (is_excluded, metadata(&path).map(|m| m.is_dir()).ok()) {
+                (true, Some(true)) => {
+                    debug!("removing excluded directory {:?}", path);
+                    remove_dir_all(&path)?;
+                }
+                (true, _) => {
+                    debug!("removing excluded file {:?}", path);
+                    remove_file(&path)?;
+                }
+                (false, Some(true)) => Self::remove_excluded(base_dir, &path, excludes)?,
+                (false, _) => {}
+            }
         }
 
         Ok(())
@@ -104,7 +860,11 @@ impl FileManager {
     fn remove_files(&self, files: &[FilePathInfo]) -> Result<()> {
         for file in files.iter().filter(|f| self.config.contains_file(f)) {
             debug!("deleting file {:?}", file.full_src_path);
-            remove_file(&file.full_repo_path)?;
+
+            if metadata(&file.full_repo_path).is_ok() {
+                self.transaction.track(&file.full_repo_path)?;
+                remove_file(&file.full_repo_path)?;
+            }
         }
 
         self.config.remove_files(files);
@@ -115,7 +875,11 @@ impl FileManager {
     fn remove_dirs(&self, dirs: &[DirPathInfo]) -> Result<()> {
         for dir in dirs.iter().filter(|f| self.config.contains_dir(f)) {
             debug!("deleting directory {:?}", dir.full_src_path);
-            remove_dir_all(&dir.full_repo_path)?;
+
+            if metadata(&dir.full_repo_path).is_ok() {
+                self.transaction.track(&dir.full_repo_path)?;
+                remove_dir_all(&dir.full_repo_path)?;
+            }
         }
 
         self.config.remove_dirs(dirs);
@@ -123,3 +887,31 @@ impl FileManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_copy_dir_skips_by_component() {
+        let dir = tempdir().unwrap();
+        let from = dir.path().join("config");
+        let to = dir.path().join("repo");
+        create_dir_all(from.join("git")).unwrap();
+        create_dir_all(from.join("github-copilot")).unwrap();
+        write(from.join("git/config"), "a").unwrap();
+        write(from.join("github-copilot/hosts.json"), "b").unwrap();
+
+        FileManager::copy_dir_skipping(&from, &to, &[from.join("git")]).unwrap();
+
+        assert!(!to.join("git").exists());
+        assert_eq!(
+            read_to_string(to.join("github-copilot/hosts.json")).unwrap(),
+            "b"
+        );
+    }
+}
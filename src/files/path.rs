@@ -1,16 +1,24 @@
 use log::debug;
-use std::fs::{create_dir_all, metadata};
-use std::path::{Component, Path, PathBuf};
+use std::cell::Cell;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{copy, create_dir_all, metadata, read_dir, remove_dir_all, remove_file, rename};
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
+use dircpy::copy_dir;
 use dirs::home_dir;
 use thiserror::Error;
 
-const ROOT_DIR_NAME: &str = ".twist";
+use super::layout::RepoLayout;
+
+const ROOT_DIR_NAME: &str = "twist";
+const LEGACY_ROOT_DIR_NAME: &str = ".twist";
+const XDG_DATA_HOME_ENV: &str = "XDG_DATA_HOME";
+const DEFAULT_DATA_HOME_DIR: &str = ".local/share";
 const FILES_DIR_NAME: &str = "dotfiles";
-const HOME_DIR_PREFIX: &str = "~";
-const HOME_DIR_MAP_NAME: &str = "home";
-const HIDDEN_FILE_PREFIX: &str = ".";
+pub const HOME_DIR_PREFIX: &str = "~";
+pub const GIT_DIR_NAME: &str = ".git";
 
 #[derive(Debug, Error)]
 enum RootDirError {
@@ -20,10 +28,37 @@ enum RootDirError {
     NotADirectory(PathBuf),
 }
 
+// the files below dir relative to it, sorted and without git metadata
+pub fn collect_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    fn collect(base_dir: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.ends_with(GIT_DIR_NAME) {
+                continue;
+            }
+
+            if metadata(&path)?.is_dir() {
+                collect(base_dir, &path, files)?;
+            } else {
+                files.push(path.strip_prefix(base_dir)?.to_path_buf());
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut files = vec![];
+    collect(dir, dir, &mut files)?;
+    files.sort();
+
+    Ok(files)
+}
+
 pub fn root_dir(root_dir_override: &Option<PathBuf>) -> Result<PathBuf> {
-    let root_dir = root_dir_override
-        .clone()
-        .or_else(|| home_dir().map(|home_dir| Path::new(&home_dir).join(ROOT_DIR_NAME)));
+    let root_dir = root_dir_override.clone().or_else(|| {
+        home_dir().map(|home_dir| default_root_dir(&home_dir, env::var_os(XDG_DATA_HOME_ENV)))
+    });
 
     match root_dir {
         Some(root_dir) => match metadata(&root_dir) {
@@ -35,11 +70,37 @@ pub fn root_dir(root_dir_override: &Option<PathBuf>) -> Result<PathBuf> {
     }
 }
 
+pub fn xdg_root_dir() -> Option<PathBuf> {
+    home_dir().map(|home_dir| xdg_root_dir_in(&home_dir, env::var_os(XDG_DATA_HOME_ENV)))
+}
+
+fn default_root_dir(home_dir: &Path, xdg_data_home: Option<OsString>) -> PathBuf {
+    let legacy_root_dir = home_dir.join(LEGACY_ROOT_DIR_NAME);
+
+    if metadata(&legacy_root_dir).is_ok() {
+        debug!("using legacy root directory {:?}", legacy_root_dir);
+        legacy_root_dir
+    } else {
+        xdg_root_dir_in(home_dir, xdg_data_home)
+    }
+}
+
+fn xdg_root_dir_in(home_dir: &Path, xdg_data_home: Option<OsString>) -> PathBuf {
+    // the spec requires relative values to be ignored
+    let data_home = xdg_data_home
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| home_dir.join(DEFAULT_DATA_HOME_DIR));
+
+    data_home.join(ROOT_DIR_NAME)
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Paths {
     pub home_dir: PathBuf,
     pub root_dir: PathBuf,
     pub files_dir: PathBuf,
+    layout: Cell<RepoLayout>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -70,7 +131,7 @@ pub enum PathsError {
 }
 
 impl Paths {
-    fn new_with_home_dir<P: AsRef<Path>>(root: P, home_dir: PathBuf) -> Self {
+    pub fn new_with_home_dir<P: AsRef<Path>>(root: P, home_dir: PathBuf) -> Self {
         let root_dir = root.as_ref().to_path_buf();
         let files_dir = root_dir.join(FILES_DIR_NAME);
 
@@ -78,6 +139,7 @@ impl Paths {
             home_dir,
             root_dir,
             files_dir,
+            layout: Cell::new(RepoLayout::default()),
         }
     }
 
@@ -116,6 +178,11 @@ impl Paths {
         &self.root_dir
     }
 
+    pub fn set_layout(&self, layout: RepoLayout) {
+        debug!("using repository layout: {:?}", layout);
+        self.layout.set(layout);
+    }
+
     pub fn resolve_file_paths<P: AsRef<Path>>(&self, p: P) -> Option<FilePathInfo> {
         let full_src_path = p.as_ref().to_path_buf();
 
@@ -150,12 +217,7 @@ impl Paths {
         let src_path = src_path.as_ref();
         let config_repo_path = config_repo_path.as_ref();
 
-        let full_src_path = if src_path.starts_with(HOME_DIR_PREFIX) {
-            self.home_dir
-                .join(src_path.strip_prefix(HOME_DIR_PREFIX).unwrap())
-        } else {
-            src_path.to_path_buf()
-        };
+        let full_src_path = self.full_src_path(src_path);
 
         FilePathInfo {
             full_src_path,
@@ -178,7 +240,6 @@ impl Paths {
         let config_repo_path = self.repo_path(&src_path);
         let repo_path = PathBuf::from(FILES_DIR_NAME).join(&config_repo_path);
         let full_repo_path = self.files_dir.join(&config_repo_path);
-        eprintln!("{:?}", full_repo_path);
         let mut full_parent_repo_path = full_repo_path.clone();
         full_parent_repo_path.pop();
 
@@ -205,12 +266,7 @@ impl Paths {
         let config_repo_path = config_repo_path.as_ref();
         let full_repo_path = self.files_dir.join(config_repo_path);
 
-        let full_src_path = if src_path.starts_with(HOME_DIR_PREFIX) {
-            self.home_dir
-                .join(src_path.strip_prefix(HOME_DIR_PREFIX).unwrap())
-        } else {
-            src_path.to_path_buf()
-        };
+        let full_src_path = self.full_src_path(src_path);
 
         DirPathInfo {
             full_src_path,
@@ -222,6 +278,15 @@ impl Paths {
         }
     }
 
+    pub fn full_src_path(&self, src_path: &Path) -> PathBuf {
+        if src_path.starts_with(HOME_DIR_PREFIX) {
+            self.home_dir
+                .join(src_path.strip_prefix(HOME_DIR_PREFIX).unwrap())
+        } else {
+            src_path.to_path_buf()
+        }
+    }
+
     fn truncate_home_path(&self, p: &Path) -> PathBuf {
         if p.starts_with(&self.home_dir) {
             Path::new(HOME_DIR_PREFIX).join(p.strip_prefix(&self.home_dir).unwrap())
@@ -230,30 +295,8 @@ impl Paths {
         }
     }
 
-    fn repo_path(&self, p: &Path) -> PathBuf {
-        let mut path = PathBuf::new();
-
-        for p in p.components() {
-            match p {
-                Component::Normal(c) if c == HOME_DIR_PREFIX => {
-                    path.push(HOME_DIR_MAP_NAME);
-                }
-                Component::Normal(c) => match c.to_str() {
-                    Some(c) if c.starts_with(HIDDEN_FILE_PREFIX) => {
-                        path.push(c.strip_prefix(HIDDEN_FILE_PREFIX).unwrap());
-                    }
-                    Some(c) => {
-                        path.push(c);
-                    }
-                    None => {
-                        panic!("invalid path component: {:?}", c);
-                    }
-                },
-                _ => {}
-            }
-        }
-
-        path
+    pub fn repo_path(&self, src_path: &Path) -> PathBuf {
+        self.layout.get().repo_path(src_path)
     }
 
     pub fn ensure_parent_dir<P: AsRef<Path>>(p: P) -> Result<()> {
@@ -270,6 +313,45 @@ impl Paths {
                 .map_err(|_| PathsError::UnableToCreateDirectory(p.to_path_buf()).into()),
         }
     }
+
+    // renames fail across file systems, so this falls back to copying and
+    // removes a partial copy when that fails too
+    pub fn move_path(from: &Path, to: &Path) -> Result<()> {
+        let Err(err) = rename(from, to) else {
+            return Ok(());
+        };
+        debug!("rename of {:?} failed, copying instead: {:?}", from, err);
+
+        let is_dir = metadata(from)?.is_dir();
+        let copied = if is_dir {
+            copy_dir(from, to)
+        } else {
+            copy(from, to).map(|_| ())
+        };
+
+        if let Err(err) = copied {
+            let cleanup = if is_dir {
+                remove_dir_all(to)
+            } else {
+                remove_file(to)
+            };
+            if let Err(cleanup_err) = cleanup {
+                debug!(
+                    "failed to remove the partial copy {:?}: {:?}",
+                    to, cleanup_err
+                );
+            }
+            return Err(err.into());
+        }
+
+        if is_dir {
+            remove_dir_all(from)?;
+        } else {
+            remove_file(from)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -283,6 +365,28 @@ mod tests {
         assert_eq!(paths.root_dir, PathBuf::from("/home/user/.twist"));
     }
 
+    #[test]
+    fn test_xdg_root_dir() {
+        let home_dir = Path::new("/home/user");
+
+        assert_eq!(
+            xdg_root_dir_in(home_dir, None),
+            PathBuf::from("/home/user/.local/share/twist")
+        );
+        assert_eq!(
+            xdg_root_dir_in(home_dir, Some(OsString::from("/data"))),
+            PathBuf::from("/data/twist")
+        );
+        assert_eq!(
+            xdg_root_dir_in(home_dir, Some(OsString::from("relative/data"))),
+            PathBuf::from("/home/user/.local/share/twist")
+        );
+        assert_eq!(
+            xdg_root_dir_in(home_dir, Some(OsString::new())),
+            PathBuf::from("/home/user/.local/share/twist")
+        );
+    }
+
     #[test]
     fn test_files_dir() {
         let paths = Paths::new(Path::new("/home/user/.twist"));
@@ -0,0 +1,154 @@
+use std::fs::read_to_string;
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Result;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::catalog::CATALOG;
+
+const PRESETS_DIR_NAME: &str = "presets";
+
+// generated or machine-specific files inside of the catalog directories
+const PRESET_EXCLUDES: [(&str, &[&str]); 5] = [
+    ("nvim", &["lazy-lock.json", "cache", "*.log"]),
+    ("vim", &[".netrwhist", "undo", "swap", "backup"]),
+    ("fish", &["fish_variables"]),
+    ("helix", &["*.log"]),
+    ("tmux", &["plugins"]),
+];
+
+#[derive(Error, Debug)]
+pub enum PresetError {
+    #[error("there is no preset for {0}, available presets: {1}")]
+    UnknownApp(String, String),
+    #[error("{0} is not a valid app name")]
+    InvalidAppName(String),
+    #[error("failed to read the preset {0}: {1}")]
+    Unreadable(PathBuf, io::Error),
+    #[error("failed to parse the preset {0}: {1}")]
+    InvalidPreset(PathBuf, String),
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub paths: Vec<PathBuf>,
+    #[serde(default)]
+    pub excludes: Vec<String>,
+}
+
+impl Preset {
+    // user presets in the repository take precedence over the built-in ones
+    pub fn load(root_dir: &Path, app: &str) -> Result<Self> {
+        // the name becomes a file name, so it must not reach outside of the presets
+        let mut components = Path::new(app).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(name)), None) if name == app
+        ) {
+            return Err(PresetError::InvalidAppName(app.to_string()).into());
+        }
+
+        let path = root_dir.join(PRESETS_DIR_NAME).join(format!("{app}.toml"));
+
+        match read_to_string(&path) {
+            Ok(content) => {
+                return toml::from_str(&content).map_err(|err| {
+                    PresetError::InvalidPreset(path, err.message().to_string()).into()
+                })
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(PresetError::Unreadable(path, err).into()),
+        }
+
+        Self::built_in(app).ok_or_else(|| {
+            PresetError::UnknownApp(app.to_string(), Self::available(root_dir).join(", ")).into()
+        })
+    }
+
+    fn built_in(app: &str) -> Option<Self> {
+        let (_, paths) = CATALOG.iter().find(|(name, _)| *name == app)?;
+        let excludes = PRESET_EXCLUDES
+            .iter()
+            .find(|(name, _)| *name == app)
+            .map(|(_, excludes)| excludes.iter().map(|e| e.to_string()).collect())
+            .unwrap_or_default();
+
+        Some(Preset {
+            paths: paths.iter().map(PathBuf::from).collect(),
+            excludes,
+        })
+    }
+
+    fn available(root_dir: &Path) -> Vec<String> {
+        let mut apps: Vec<String> = CATALOG.iter().map(|(app, _)| app.to_string()).collect();
+
+        if let Ok(entries) = root_dir.join(PRESETS_DIR_NAME).read_dir() {
+            apps.extend(entries.filter_map(|e| e.ok()).filter_map(|e| {
+                let path = e.path();
+                (path.extension()? == "toml")
+                    .then(|| path.file_stem()?.to_str().map(|s| s.to_string()))?
+            }));
+        }
+
+        apps.sort();
+        apps.dedup();
+        apps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_built_in_preset() {
+        let preset = Preset::built_in("nvim").unwrap();
+
+        assert_eq!(preset.paths, vec![PathBuf::from("~/.config/nvim")]);
+        assert!(preset.excludes.contains(&"lazy-lock.json".to_string()));
+        assert_eq!(
+            Preset::built_in("git").unwrap().excludes,
+            Vec::<String>::new()
+        );
+        assert!(Preset::built_in("unknown").is_none());
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = tempdir().unwrap();
+        let presets_dir = dir.path().join(PRESETS_DIR_NAME);
+        create_dir_all(presets_dir.join("broken.toml")).unwrap();
+        write(
+            presets_dir.join("nvim.toml"),
+            "paths = [\"~/.config/nvim\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Preset::load(dir.path(), "nvim").unwrap().excludes,
+            Vec::<String>::new()
+        );
+        assert!(Preset::load(dir.path(), "git").is_ok());
+
+        let err = Preset::load(dir.path(), "broken").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(PresetError::Unreadable(_, _))
+        ));
+
+        for app in ["../nvim", "nvim/", "/nvim", "..", ".", ""] {
+            let err = Preset::load(dir.path(), app).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref(),
+                Some(PresetError::InvalidAppName(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+const VARIABLE_START: &str = "{{";
+const VARIABLE_END: &str = "}}";
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("unknown template variable: {0}")]
+    UnknownVariable(String),
+    #[error("unterminated template variable at offset {0}")]
+    Unterminated(usize),
+}
+
+// replaces every {{ name }} with the value of the variable
+pub fn render(
+    content: &str,
+    variables: &BTreeMap<String, String>,
+) -> Result<String, TemplateError> {
+    let mut rendered = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find(VARIABLE_START) {
+        rendered.push_str(&rest[..start]);
+
+        let after_start = &rest[start + VARIABLE_START.len()..];
+        let end = after_start
+            .find(VARIABLE_END)
+            .ok_or_else(|| TemplateError::Unterminated(content.len() - rest.len() + start))?;
+
+        let name = after_start[..end].trim();
+        let value = variables
+            .get(name)
+            .ok_or_else(|| TemplateError::UnknownVariable(name.to_string()))?;
+        rendered.push_str(value);
+
+        rest = &after_start[end + VARIABLE_END.len()..];
+    }
+
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let variables = BTreeMap::from([("email".to_string(), "me@home".to_string())]);
+
+        assert_eq!(
+            render("email = {{ email }}\nname = {{email}}", &variables),
+            Ok("email = me@home\nname = me@home".to_string())
+        );
+        assert_eq!(
+            render("{{ name }}", &variables),
+            Err(TemplateError::UnknownVariable("name".to_string()))
+        );
+        assert_eq!(
+            render("a {{ email", &variables),
+            Err(TemplateError::Unterminated(2))
+        );
+    }
+}
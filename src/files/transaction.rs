@@ -0,0 +1,135 @@
+use std::cell::{Cell, RefCell};
+use std::fs::{copy, create_dir_all, metadata, remove_dir_all, remove_file};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use dircpy::copy_dir;
+use log::{debug, warn};
+
+use super::path::{Paths, GIT_DIR_NAME};
+
+const BACKUP_DIR_NAME: &str = "twist-transaction";
+
+struct JournalEntry {
+    path: PathBuf,
+    backup_path: Option<PathBuf>,
+}
+
+pub struct Transaction {
+    backup_dir: PathBuf,
+    active: Cell<bool>,
+    journal: RefCell<Vec<JournalEntry>>,
+}
+
+impl Transaction {
+    pub fn new(paths: &Paths) -> Self {
+        // backups live in the git directory so they are never committed
+        let backup_dir = paths.root_dir.join(GIT_DIR_NAME).join(BACKUP_DIR_NAME);
+
+        Transaction {
+            backup_dir,
+            active: Cell::new(false),
+            journal: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn begin(&self) -> Result<()> {
+        if metadata(&self.backup_dir).is_ok() {
+            warn!(
+                "removing the backups of an unfinished transaction at {:?}",
+                self.backup_dir
+            );
+            remove_dir_all(&self.backup_dir)?;
+        }
+
+        debug!("beginning transaction");
+        create_dir_all(&self.backup_dir)?;
+        self.journal.borrow_mut().clear();
+        self.active.set(true);
+
+        Ok(())
+    }
+
+    pub fn track<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        if !self.active.get() {
+            return Ok(());
+        }
+
+        // a missing path is journaled as its first missing ancestor, so that a
+        // rollback also removes the parent directories created for it
+        let mut path = path.as_ref();
+        while metadata(path).is_err() {
+            match path.parent() {
+                Some(parent) if metadata(parent).is_err() => path = parent,
+                _ => break,
+            }
+        }
+
+        let mut journal = self.journal.borrow_mut();
+
+        // a path inside of a tracked directory is already covered by its backup
+        if journal.iter().any(|e| path.starts_with(&e.path)) {
+            return Ok(());
+        }
+
+        let backup_path = match metadata(path) {
+            Ok(m) => {
+                let backup_path = self.backup_dir.join(journal.len().to_string());
+                debug!("backing up {:?} to {:?}", path, backup_path);
+
+                if m.is_dir() {
+                    copy_dir(path, &backup_path)?;
+                } else {
+                    copy(path, &backup_path)?;
+                }
+
+                Some(backup_path)
+            }
+            Err(_) => None,
+        };
+
+        journal.push(JournalEntry {
+            path: path.to_path_buf(),
+            backup_path,
+        });
+
+        Ok(())
+    }
+
+    pub fn commit(&self) -> Result<()> {
+        debug!("committing transaction");
+        self.finish()
+    }
+
+    pub fn rollback(&self) -> Result<()> {
+        debug!("rolling back transaction");
+
+        // restoring in reverse order undoes nested changes before their parents
+        for entry in self.journal.borrow_mut().drain(..).rev() {
+            match metadata(&entry.path).map(|m| m.is_dir()).ok() {
+                Some(true) => remove_dir_all(&entry.path)?,
+                Some(false) => remove_file(&entry.path)?,
+                None => {}
+            }
+
+            if let Some(backup_path) = entry.backup_path {
+                debug!("restoring {:?} from {:?}", entry.path, backup_path);
+                Paths::ensure_parent_dir(&entry.path)?;
+                Paths::move_path(&backup_path, &entry.path)?;
+            }
+        }
+
+        self.finish()
+    }
+
+    fn finish(&self) -> Result<()> {
+        self.active.set(false);
+        self.journal.borrow_mut().clear();
+
+        if metadata(&self.backup_dir).is_ok() {
+            remove_dir_all(&self.backup_dir)?;
+        }
+
+        Ok(())
+    }
+}
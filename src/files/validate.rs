@@ -0,0 +1,215 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use serde::Deserialize;
+use subprocess::{Exec, Redirection};
+
+const NO_VALIDATOR: &str = "none";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Validator {
+    Toml,
+    Json,
+    Yaml,
+    Ini,
+    Shell,
+    Command(String),
+}
+
+impl Validator {
+    // the entry setting wins over the extension, any other value is a command
+    // that gets the path of the file appended
+    pub fn for_path(path: &Path, setting: Option<&str>) -> Option<Self> {
+        match setting {
+            Some(NO_VALIDATOR) => None,
+            Some(setting) => {
+                Some(Self::from_name(setting).unwrap_or(Validator::Command(setting.to_string())))
+            }
+            None => Self::from_name(path.extension()?.to_str()?),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "toml" => Some(Validator::Toml),
+            "json" => Some(Validator::Json),
+            "yaml" | "yml" => Some(Validator::Yaml),
+            "ini" => Some(Validator::Ini),
+            "sh" => Some(Validator::Shell),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self, path: &Path) -> Result<(), String> {
+        let content = || read_to_string(path).map_err(|err| err.to_string());
+
+        match self {
+            Validator::Toml => toml::from_str::<toml::Table>(&content()?)
+                .map(|_| ())
+                .map_err(|err| err.to_string().trim().to_string()),
+            Validator::Json => serde_json::from_str::<serde_json::Value>(&strip_jsonc(&content()?))
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            Validator::Yaml => {
+                for document in serde_norway::Deserializer::from_str(&content()?) {
+                    serde_norway::Value::deserialize(document).map_err(|err| err.to_string())?;
+                }
+                Ok(())
+            }
+            Validator::Ini => validate_ini(&content()?),
+            Validator::Shell => run(Exec::cmd("sh").arg("-n").arg(path)),
+            Validator::Command(command) => run(Exec::cmd("sh")
+                .arg("-c")
+                .arg(format!("{command} \"$1\""))
+                .arg("sh")
+                .arg(path)),
+        }
+    }
+}
+
+fn run(exec: Exec) -> Result<(), String> {
+    let capture = exec
+        .stdout(Redirection::Pipe)
+        .stderr(Redirection::Merge)
+        .capture()
+        .map_err(|err| err.to_string())?;
+
+    if capture.success() {
+        Ok(())
+    } else {
+        Err(capture.stdout_str().trim().to_string())
+    }
+}
+
+// editors such as VS Code allow comments and trailing commas in their JSON files,
+// they are blanked out so that the positions in errors still match the file
+fn strip_jsonc(content: &str) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let mut stripped = chars.clone();
+    let mut in_string = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('\\', _) if in_string => i += 1,
+            ('"', _) => in_string = !in_string,
+            ('/', Some('/')) if !in_string => {
+                while i < chars.len() && chars[i] != '\n' {
+                    stripped[i] = ' ';
+                    i += 1;
+                }
+                continue;
+            }
+            ('/', Some('*')) if !in_string => {
+                // an unterminated comment is left for the parser to report
+                let Some(end) = (i + 2..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                else {
+                    break;
+                };
+                for j in i..end + 2 {
+                    if chars[j] != '\n' {
+                        stripped[j] = ' ';
+                    }
+                }
+                i = end + 2;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let mut in_string = false;
+    let mut i = 0;
+
+    while i < stripped.len() {
+        match stripped[i] {
+            '\\' if in_string => i += 1,
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                let next = stripped[i + 1..].iter().find(|c| !c.is_whitespace());
+                if matches!(next, Some('}' | ']')) {
+                    stripped[i] = ' ';
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    stripped.into_iter().collect()
+}
+
+fn validate_ini(content: &str) -> Result<(), String> {
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let valid = line.is_empty()
+            || line.starts_with([';', '#'])
+            || (line.starts_with('[') && line.ends_with(']'))
+            || line
+                .split_once(['=', ':'])
+                .is_some_and(|(key, _)| !key.trim().is_empty());
+
+        if !valid {
+            return Err(format!(
+                "line {}: expected a section, a key-value pair or a comment",
+                number + 1
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validator_for_path() {
+        let path = Path::new("home/config/starship.toml");
+
+        assert_eq!(Validator::for_path(path, None), Some(Validator::Toml));
+        assert_eq!(Validator::for_path(path, Some("none")), None);
+        assert_eq!(Validator::for_path(Path::new("home/bashrc"), None), None);
+        assert_eq!(
+            Validator::for_path(Path::new("home/bashrc"), Some("sh")),
+            Some(Validator::Shell)
+        );
+        assert_eq!(
+            Validator::for_path(path, Some("taplo check")),
+            Some(Validator::Command("taplo check".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_strip_jsonc() {
+        let content = r#"{
+    // the theme
+    "workbench.colorTheme": "Default Dark+", /* a block
+    comment */
+    "http.proxy": "http://localhost:8080", // "quoted"
+    "files.exclude": {"**/.git": true,},
+}
+"#;
+        let stripped = strip_jsonc(content);
+
+        assert_eq!(stripped.lines().count(), content.lines().count());
+        let value: serde_json::Value = serde_json::from_str(&stripped).unwrap();
+        assert_eq!(value["http.proxy"], "http://localhost:8080");
+        assert_eq!(value["files.exclude"]["**/.git"], true);
+
+        assert!(serde_json::from_str::<serde_json::Value>(&strip_jsonc("{\"a\": 1 /* x")).is_err());
+        assert!(serde_json::from_str::<serde_json::Value>(&strip_jsonc("[1,,]")).is_err());
+    }
+
+    #[test]
+    fn test_validate_ini() {
+        assert!(validate_ini("; comment\n[user]\n\tname = twist\nemail: a@b\n").is_ok());
+        assert_eq!(
+            validate_ini("[user]\nname twist\n"),
+            Err("line 2: expected a section, a key-value pair or a comment".to_string())
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use super::{home_path, Import, ImportedContent, Importer};
+use crate::config::EntryOptions;
+use crate::files::path::collect_files;
+
+const SPECIAL_FILE_PREFIX: &str = ".chezmoi";
+const IGNORED_FILE_PREFIX: &str = ".";
+const DOT_PREFIX: &str = "dot_";
+const PRIVATE_PREFIX: &str = "private_";
+const READONLY_PREFIX: &str = "readonly_";
+const EXECUTABLE_PREFIX: &str = "executable_";
+const EMPTY_PREFIX: &str = "empty_";
+const LITERAL_PREFIX: &str = "literal_";
+const TEMPLATE_SUFFIX: &str = ".tmpl";
+const LITERAL_SUFFIX: &str = ".literal";
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+// attributes twist has no equivalent for, the files are skipped
+const UNSUPPORTED_PREFIXES: [(&str, &str); 12] = [
+    ("create_", "only created when missing"),
+    ("modify_", "a modify script"),
+    ("remove_", "a removal"),
+    ("run_", "a script"),
+    ("once_", "a script"),
+    ("onchange_", "a script"),
+    ("before_", "a script"),
+    ("after_", "a script"),
+    ("symlink_", "a symlink"),
+    ("encrypted_", "encrypted"),
+    ("exact_", "an exact directory"),
+    ("external_", "an external"),
+];
+
+#[derive(Default)]
+struct Attributes {
+    private: bool,
+    readonly: bool,
+    executable: bool,
+    dir_permissions: bool,
+    unsupported: Option<&'static str>,
+}
+
+pub struct ChezmoiImporter;
+
+impl Importer for ChezmoiImporter {
+    fn read(path: &Path) -> Result<Import> {
+        let mut import = Import::default();
+
+        for relative_path in collect_files(path)? {
+            let first = relative_path.components().next();
+            let first = first.map(|c| c.as_os_str().to_string_lossy().to_string());
+
+            match first {
+                Some(first) if first.starts_with(SPECIAL_FILE_PREFIX) => {
+                    import.skip(format!(
+                        "{} is chezmoi configuration, it was not translated",
+                        relative_path.display()
+                    ));
+                    continue;
+                }
+                Some(first) if first.starts_with(IGNORED_FILE_PREFIX) => continue,
+                _ => {}
+            }
+
+            let (target_path, attributes) = Self::parse(&relative_path);
+
+            if let Some(reason) = attributes.unsupported {
+                import.skip(format!(
+                    "{} is {}, it was not translated",
+                    relative_path.display(),
+                    reason
+                ));
+                continue;
+            }
+
+            if attributes.dir_permissions {
+                import.skip(format!(
+                    "the directory permissions of {} were not translated",
+                    relative_path.display()
+                ));
+            }
+
+            let mut options = EntryOptions::default();
+            if attributes.private || attributes.readonly || attributes.executable {
+                let mut mode = DEFAULT_FILE_MODE;
+                if attributes.executable {
+                    mode |= 0o111;
+                }
+                if attributes.private {
+                    mode &= 0o700;
+                }
+                if attributes.readonly {
+                    mode &= !0o222;
+                }
+                options.mode = Some(format!("{:o}", mode));
+            }
+
+            import.add(
+                home_path(&target_path),
+                ImportedContent::File(path.join(&relative_path)),
+                options,
+            );
+        }
+
+        Ok(import)
+    }
+}
+
+impl ChezmoiImporter {
+    fn parse(relative_path: &Path) -> (PathBuf, Attributes) {
+        let mut attributes = Attributes::default();
+        let mut target_path = PathBuf::new();
+        let count = relative_path.components().count();
+
+        for (i, component) in relative_path.components().enumerate() {
+            let mut name = component.as_os_str().to_string_lossy().to_string();
+            let is_file = i == count - 1;
+
+            if is_file {
+                if let Some(stripped) = name.strip_suffix(LITERAL_SUFFIX) {
+                    name = stripped.to_string();
+                } else if name.ends_with(TEMPLATE_SUFFIX) {
+                    attributes.unsupported = Some("a chezmoi template");
+                }
+            }
+
+            // the attributes are prefixes that are stripped until none is left
+            loop {
+                if let Some(stripped) = name.strip_prefix(LITERAL_PREFIX) {
+                    name = stripped.to_string();
+                    break;
+                }
+
+                let before = name.len();
+                for (prefix, reason) in UNSUPPORTED_PREFIXES {
+                    if let Some(stripped) = name.strip_prefix(prefix) {
+                        name = stripped.to_string();
+                        attributes.unsupported.get_or_insert(reason);
+                    }
+                }
+                if let Some(stripped) = name.strip_prefix(PRIVATE_PREFIX) {
+                    name = stripped.to_string();
+                    attributes.private |= is_file;
+                    attributes.dir_permissions |= !is_file;
+                }
+                if let Some(stripped) = name.strip_prefix(READONLY_PREFIX) {
+                    name = stripped.to_string();
+                    attributes.readonly |= is_file;
+                    attributes.dir_permissions |= !is_file;
+                }
+                if let Some(stripped) = name.strip_prefix(EXECUTABLE_PREFIX) {
+                    name = stripped.to_string();
+                    attributes.executable |= is_file;
+                }
+                if let Some(stripped) = name.strip_prefix(EMPTY_PREFIX) {
+                    name = stripped.to_string();
+                }
+                if let Some(stripped) = name.strip_prefix(DOT_PREFIX) {
+                    name = format!(".{}", stripped);
+                    break;
+                }
+
+                if name.len() == before {
+                    break;
+                }
+            }
+
+            target_path.push(name);
+        }
+
+        (target_path, attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let (path, attributes) =
+            ChezmoiImporter::parse(Path::new("private_dot_ssh/private_executable_dot_rc"));
+        assert_eq!(path, PathBuf::from(".ssh/.rc"));
+        assert!(attributes.private && attributes.executable);
+        assert!(attributes.unsupported.is_none());
+
+        let (path, attributes) = ChezmoiImporter::parse(Path::new("dot_config/literal_dot_x"));
+        assert_eq!(path, PathBuf::from(".config/dot_x"));
+        assert!(!attributes.private);
+
+        let (_, attributes) = ChezmoiImporter::parse(Path::new("dot_gitconfig.tmpl"));
+        assert!(attributes.unsupported.is_some());
+
+        let (_, attributes) = ChezmoiImporter::parse(Path::new("run_once_install.sh"));
+        assert!(attributes.unsupported.is_some());
+    }
+}
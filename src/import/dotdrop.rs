@@ -0,0 +1,267 @@
+use std::fs::{metadata, read, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use dirs::home_dir;
+use serde_norway::{Mapping, Value};
+use thiserror::Error;
+
+use super::{Import, ImportedContent, Importer};
+use crate::config::EntryOptions;
+use crate::files::path::{collect_files, HOME_DIR_PREFIX};
+
+const CONFIG_FILE_NAME: &str = "config.yaml";
+const DEFAULT_DOTPATH: &str = "dotfiles";
+const HOME_ENV_PREFIXES: [&str; 2] = ["${HOME}", "$HOME"];
+const TEMPLATE_MARKERS: [&str; 3] = ["{{@@", "{%@@", "{#@@"];
+const NO_LINK_VALUES: [&str; 2] = ["nolink", "false"];
+const IGNORED_KEYS: [&str; 3] = ["src", "dst", "ignoreempty"];
+const UNTRANSLATED_SECTIONS: [&str; 8] = [
+    "actions",
+    "trans_read",
+    "trans_write",
+    "trans_install",
+    "trans_update",
+    "variables",
+    "dynvariables",
+    "uservariables",
+];
+
+#[derive(Error, Debug)]
+enum DotdropError {
+    #[error("failed to parse {0}: {1}")]
+    InvalidConfig(PathBuf, String),
+    #[error("{0} has no dotfiles section")]
+    NoDotfiles(PathBuf),
+}
+
+pub struct DotdropImporter;
+
+impl Importer for DotdropImporter {
+    fn read(path: &Path) -> Result<Import> {
+        let config_path = match metadata(path) {
+            Ok(m) if m.is_dir() => path.join(CONFIG_FILE_NAME),
+            _ => path.to_path_buf(),
+        };
+        let base_dir = config_path.parent().unwrap_or(Path::new(""));
+
+        let config: Value = serde_norway::from_reader(File::open(&config_path)?)
+            .map_err(|err| DotdropError::InvalidConfig(config_path.clone(), err.to_string()))?;
+
+        let dotpath = config
+            .get("config")
+            .and_then(|c| c.get("dotpath"))
+            .and_then(Value::as_str)
+            .unwrap_or(DEFAULT_DOTPATH);
+        let dotpath = base_dir.join(dotpath);
+
+        let dotfiles = config
+            .get("dotfiles")
+            .and_then(Value::as_mapping)
+            .ok_or_else(|| DotdropError::NoDotfiles(config_path.clone()))?;
+
+        let mut import = Import::default();
+
+        for section in UNTRANSLATED_SECTIONS {
+            if config
+                .get(section)
+                .is_some_and(|v| !v.as_mapping().is_some_and(Mapping::is_empty))
+            {
+                import.skip(format!("the {} section was not translated", section));
+            }
+        }
+
+        if let Some(profiles) = config.get("profiles").and_then(Value::as_mapping) {
+            if profiles.len() > 1 {
+                import.skip(format!(
+                    "the {} profiles were not translated, the dotfiles of all of them were imported",
+                    profiles.len()
+                ));
+            }
+        }
+
+        for (key, dotfile) in dotfiles {
+            let key = key.as_str().unwrap_or_default();
+            Self::read_dotfile(key, dotfile, &dotpath, &mut import)?;
+        }
+
+        Ok(import)
+    }
+}
+
+impl DotdropImporter {
+    fn read_dotfile(key: &str, dotfile: &Value, dotpath: &Path, import: &mut Import) -> Result<()> {
+        let (src, dst) = match (
+            dotfile.get("src").and_then(Value::as_str),
+            dotfile.get("dst").and_then(Value::as_str),
+        ) {
+            (Some(src), Some(dst)) => (dotpath.join(src), dst),
+            _ => {
+                import.skip(format!("{} has no src or dst, it was not imported", key));
+                return Ok(());
+            }
+        };
+
+        let is_dir = match metadata(&src) {
+            Ok(m) => m.is_dir(),
+            Err(_) => {
+                import.skip(format!("{} is missing {:?}, it was not imported", key, src));
+                return Ok(());
+            }
+        };
+
+        if Self::is_template(&src, is_dir)? {
+            import.skip(format!(
+                "{} uses dotdrop templates, it was not imported",
+                key
+            ));
+            return Ok(());
+        }
+
+        let mut options = EntryOptions::default();
+
+        for (option, value) in dotfile.as_mapping().into_iter().flatten() {
+            let option = option.as_str().unwrap_or_default();
+
+            match option {
+                "chmod" => {
+                    let mode = match value {
+                        Value::Number(n) => n.to_string(),
+                        value => value.as_str().unwrap_or_default().to_string(),
+                    };
+                    if options.set("mode", &mode).is_err() {
+                        import.skip(format!("the chmod {} of {} is invalid", mode, key));
+                    }
+                }
+                "upignore" => options.excludes.extend(
+                    value
+                        .as_sequence()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string),
+                ),
+                "link" if value.as_str().is_some_and(|v| NO_LINK_VALUES.contains(&v)) => {}
+                "template" if value.as_bool() == Some(false) => {}
+                _ if IGNORED_KEYS.contains(&option) => {}
+                _ => import.skip(format!(
+                    "the {} option of {} was not translated",
+                    option, key
+                )),
+            }
+        }
+
+        let content = if is_dir {
+            ImportedContent::Dir(src)
+        } else {
+            ImportedContent::File(src)
+        };
+        import.add(Self::src_path(dst), content, options);
+
+        Ok(())
+    }
+
+    fn is_template(src: &Path, is_dir: bool) -> Result<bool> {
+        let files = if is_dir {
+            collect_files(src)?.iter().map(|f| src.join(f)).collect()
+        } else {
+            vec![src.to_path_buf()]
+        };
+
+        for file in files {
+            let content = String::from_utf8_lossy(&read(file)?).to_string();
+            if TEMPLATE_MARKERS.iter().any(|m| content.contains(m)) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn src_path(dst: &str) -> PathBuf {
+        for prefix in HOME_ENV_PREFIXES {
+            if let Some(rest) = dst.strip_prefix(prefix) {
+                return Path::new(HOME_DIR_PREFIX).join(rest.trim_start_matches('/'));
+            }
+        }
+
+        let dst = PathBuf::from(dst);
+        match home_dir().and_then(|h| dst.strip_prefix(h).ok().map(Path::to_path_buf)) {
+            Some(rest) => Path::new(HOME_DIR_PREFIX).join(rest),
+            None => dst,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const CONFIG: &str = "
+config:
+  dotpath: dots
+dotfiles:
+  f_zshrc:
+    src: zshrc
+    dst: $HOME/.zshrc
+    chmod: 600
+  d_nvim:
+    src: nvim
+    dst: ${HOME}/.config/nvim
+    upignore:
+      - '*.log'
+  f_gitconfig:
+    src: gitconfig
+    dst: ~/.gitconfig
+";
+
+    #[test]
+    fn test_read() {
+        let dir = tempdir().unwrap();
+        let dots_dir = dir.path().join("dots");
+        create_dir_all(dots_dir.join("nvim")).unwrap();
+        write(dir.path().join(CONFIG_FILE_NAME), CONFIG).unwrap();
+        write(dots_dir.join("zshrc"), "export EDITOR=vim\n").unwrap();
+        write(dots_dir.join("nvim/init.lua"), "vim.o.number = true\n").unwrap();
+        write(dots_dir.join("gitconfig"), "name = {{@@ user @@}}\n").unwrap();
+
+        let import = DotdropImporter::read(dir.path()).unwrap();
+
+        let entries: Vec<_> = import
+            .entries
+            .iter()
+            .map(|e| {
+                (
+                    e.src_path.clone(),
+                    e.options.mode.clone(),
+                    e.options.excludes.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("~/.zshrc"), Some("600".to_string()), vec![]),
+                (
+                    PathBuf::from("~/.config/nvim"),
+                    None,
+                    vec!["*.log".to_string()]
+                ),
+            ]
+        );
+        assert!(
+            matches!(&import.entries[0].content, ImportedContent::File(p) if *p == dots_dir.join("zshrc"))
+        );
+        assert!(
+            matches!(&import.entries[1].content, ImportedContent::Dir(p) if *p == dots_dir.join("nvim"))
+        );
+        assert_eq!(
+            import.untranslated,
+            vec!["f_gitconfig uses dotdrop templates, it was not imported"]
+        );
+    }
+}
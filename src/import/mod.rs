@@ -0,0 +1,86 @@
+mod chezmoi;
+mod dotdrop;
+mod stow;
+mod yadm;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::config::EntryOptions;
+use crate::files::path::HOME_DIR_PREFIX;
+
+use self::chezmoi::ChezmoiImporter;
+use self::dotdrop::DotdropImporter;
+use self::stow::StowImporter;
+use self::yadm::YadmImporter;
+
+#[derive(Debug)]
+pub enum ImportedContent {
+    File(PathBuf),
+    Dir(PathBuf),
+    Blob(Vec<u8>),
+}
+
+#[derive(Debug)]
+pub struct ImportedEntry {
+    pub src_path: PathBuf,
+    pub content: ImportedContent,
+    pub options: EntryOptions,
+}
+
+#[derive(Debug, Default)]
+pub struct Import {
+    pub entries: Vec<ImportedEntry>,
+    pub untranslated: Vec<String>,
+}
+
+impl Import {
+    fn add(&mut self, src_path: PathBuf, content: ImportedContent, options: EntryOptions) {
+        self.entries.push(ImportedEntry {
+            src_path,
+            content,
+            options,
+        });
+    }
+
+    fn skip(&mut self, reason: String) {
+        self.untranslated.push(reason);
+    }
+}
+
+trait Importer {
+    fn read(path: &Path) -> Result<Import>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportSource {
+    Dotdrop,
+    Chezmoi,
+    Stow,
+    Yadm,
+}
+
+impl ImportSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportSource::Dotdrop => "dotdrop",
+            ImportSource::Chezmoi => "chezmoi",
+            ImportSource::Stow => "stow",
+            ImportSource::Yadm => "yadm",
+        }
+    }
+
+    pub fn read(self, path: &Path) -> Result<Import> {
+        match self {
+            ImportSource::Dotdrop => DotdropImporter::read(path),
+            ImportSource::Chezmoi => ChezmoiImporter::read(path),
+            ImportSource::Stow => StowImporter::read(path),
+            ImportSource::Yadm => YadmImporter::read(path),
+        }
+    }
+}
+
+fn home_path(relative_path: &Path) -> PathBuf {
+    Path::new(HOME_DIR_PREFIX).join(relative_path)
+}
@@ -0,0 +1,164 @@
+use std::fs::{metadata, read_dir};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use glob::Pattern;
+
+use super::{home_path, Import, ImportedContent, Importer};
+use crate::config::EntryOptions;
+use crate::files::path::collect_files;
+
+const LOCAL_IGNORE_FILE_NAME: &str = ".stow-local-ignore";
+const DOT_PREFIX: &str = "dot-";
+const IGNORED_NAMES: [&str; 8] = [
+    ".git",
+    ".gitignore",
+    ".gitmodules",
+    ".cvsignore",
+    LOCAL_IGNORE_FILE_NAME,
+    "*~",
+    ".#*",
+    "#*#",
+];
+const IGNORED_TOP_LEVEL_NAMES: [&str; 3] = ["README*", "LICENSE*", "COPYING"];
+
+pub struct StowImporter;
+
+impl Importer for StowImporter {
+    fn read(path: &Path) -> Result<Import> {
+        let mut import = Import::default();
+
+        let mut packages: Vec<_> = read_dir(path)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        packages.sort();
+
+        for package_path in packages {
+            let package = package_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if package.starts_with('.') {
+                continue;
+            }
+
+            if !metadata(&package_path)?.is_dir() {
+                import.skip(format!("{} is not a package, it was not imported", package));
+                continue;
+            }
+
+            if metadata(package_path.join(LOCAL_IGNORE_FILE_NAME)).is_ok() {
+                import.skip(format!(
+                    "the ignore list of {} was not translated, only the default one was applied",
+                    package
+                ));
+            }
+
+            for relative_path in collect_files(&package_path)? {
+                if Self::is_ignored(&relative_path) {
+                    continue;
+                }
+
+                let options = EntryOptions {
+                    tags: vec![package.clone()],
+                    ..EntryOptions::default()
+                };
+
+                import.add(
+                    home_path(&Self::target_path(&relative_path)),
+                    ImportedContent::File(package_path.join(&relative_path)),
+                    options,
+                );
+            }
+        }
+
+        Ok(import)
+    }
+}
+
+impl StowImporter {
+    // stow's default ignore list
+    fn is_ignored(relative_path: &Path) -> bool {
+        let matches = |patterns: &[&str], name: &str| {
+            patterns
+                .iter()
+                .filter_map(|p| Pattern::new(p).ok())
+                .any(|p| p.matches(name))
+        };
+
+        let names: Vec<_> = relative_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+
+        names.iter().any(|name| matches(&IGNORED_NAMES, name))
+            || (names.len() == 1 && matches(&IGNORED_TOP_LEVEL_NAMES, &names[0]))
+    }
+
+    // packages written for `stow --dotfiles` name hidden files dot-*
+    fn target_path(relative_path: &Path) -> PathBuf {
+        relative_path
+            .components()
+            .map(|c| {
+                let name = c.as_os_str().to_string_lossy();
+                match name.strip_prefix(DOT_PREFIX) {
+                    Some(name) => format!(".{}", name),
+                    None => name.to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_target_path() {
+        assert_eq!(
+            StowImporter::target_path(Path::new("dot-config/nvim/dot-init.lua")),
+            PathBuf::from(".config/nvim/.init.lua")
+        );
+        assert_eq!(
+            StowImporter::target_path(Path::new("bin/dotfiles")),
+            PathBuf::from("bin/dotfiles")
+        );
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        assert!(StowImporter::is_ignored(Path::new("README.md")));
+        assert!(StowImporter::is_ignored(Path::new(".gitignore")));
+        assert!(StowImporter::is_ignored(Path::new(
+            "dot-config/nvim/init.lua~"
+        )));
+        assert!(StowImporter::is_ignored(Path::new("dot-config/.#init.lua")));
+        assert!(!StowImporter::is_ignored(Path::new("docs/README.md")));
+        assert!(!StowImporter::is_ignored(Path::new("dot-zshrc")));
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = tempdir().unwrap();
+        create_dir_all(dir.path().join("zsh")).unwrap();
+        write(dir.path().join("zsh/dot-zshrc"), "export EDITOR=vim\n").unwrap();
+        write(dir.path().join("zsh/README"), "zsh\n").unwrap();
+        write(dir.path().join("notes.txt"), "notes\n").unwrap();
+
+        let import = StowImporter::read(dir.path()).unwrap();
+
+        assert_eq!(import.entries.len(), 1);
+        assert_eq!(import.entries[0].src_path, PathBuf::from("~/.zshrc"));
+        assert_eq!(import.entries[0].options.tags, vec!["zsh"]);
+        assert_eq!(
+            import.untranslated,
+            vec!["notes.txt is not a package, it was not imported"]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use git2::{ObjectType, Repository, TreeWalkMode, TreeWalkResult};
+
+use super::{home_path, Import, ImportedContent, Importer};
+use crate::config::EntryOptions;
+
+const ALTERNATE_SEPARATOR: &str = "##";
+const YADM_DIRS: [&str; 2] = [".config/yadm", ".yadm"];
+const EXECUTABLE_FILE_MODE: i32 = 0o100755;
+const SYMLINK_FILE_MODE: i32 = 0o120000;
+
+pub struct YadmImporter;
+
+impl Importer for YadmImporter {
+    fn read(path: &Path) -> Result<Import> {
+        let repo = Repository::open(path)?;
+        let tree = repo.head()?.peel_to_tree()?;
+
+        let mut blobs = vec![];
+        tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(ObjectType::Blob) {
+                let name = entry.name().unwrap_or_default();
+                blobs.push((PathBuf::from(dir).join(name), entry.id(), entry.filemode()));
+            }
+            TreeWalkResult::Ok
+        })?;
+
+        let mut import = Import::default();
+
+        for (relative_path, id, filemode) in blobs {
+            let display = relative_path.display();
+
+            if YADM_DIRS.iter().any(|d| relative_path.starts_with(d)) {
+                import.skip(format!(
+                    "{} is yadm configuration, it was not translated",
+                    display
+                ));
+                continue;
+            }
+
+            if relative_path
+                .to_string_lossy()
+                .contains(ALTERNATE_SEPARATOR)
+            {
+                import.skip(format!(
+                    "{} is a yadm alternate or template, it was not translated",
+                    display
+                ));
+                continue;
+            }
+
+            if filemode == SYMLINK_FILE_MODE {
+                import.skip(format!("{} is a symlink, it was not translated", display));
+                continue;
+            }
+
+            let mut options = EntryOptions::default();
+            if filemode == EXECUTABLE_FILE_MODE {
+                options.mode = Some(format!("{:o}", EXECUTABLE_FILE_MODE & 0o777));
+            }
+
+            let content = repo.find_blob(id)?.content().to_vec();
+            import.add(
+                home_path(&relative_path),
+                ImportedContent::Blob(content),
+                options,
+            );
+        }
+
+        Ok(import)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, set_permissions, write, Permissions};
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    use git2::Signature;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_read() {
+        let dir = tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let files = [
+            ".zshrc",
+            ".local/bin/hello",
+            ".gitconfig##os.Linux",
+            ".config/yadm/bootstrap",
+        ];
+        for file in files {
+            let path = dir.path().join(file);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(&path, "contents\n").unwrap();
+        }
+        let hello = dir.path().join(".local/bin/hello");
+        set_permissions(&hello, Permissions::from_mode(0o755)).unwrap();
+        symlink(".zshrc", dir.path().join(".zprofile")).unwrap();
+
+        let mut index = repo.index().unwrap();
+        for file in files.iter().chain(&[".zprofile"]) {
+            index.add_path(Path::new(file)).unwrap();
+        }
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "yadm", &tree, &[])
+            .unwrap();
+
+        let import = YadmImporter::read(dir.path()).unwrap();
+
+        let entries: Vec<_> = import
+            .entries
+            .iter()
+            .map(|e| (e.src_path.clone(), e.options.mode.clone()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("~/.local/bin/hello"), Some("755".to_string())),
+                (PathBuf::from("~/.zshrc"), None),
+            ]
+        );
+        assert_eq!(
+            import.untranslated,
+            vec![
+                ".config/yadm/bootstrap is yadm configuration, it was not translated",
+                ".gitconfig##os.Linux is a yadm alternate or template, it was not translated",
+                ".zprofile is a symlink, it was not translated",
+            ]
+        );
+    }
+}
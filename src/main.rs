@@ -2,6 +2,7 @@ mod cli;
 mod commands;
 mod config;
 mod files;
+mod import;
 
 use anyhow::Result;
 use clap::Parser;